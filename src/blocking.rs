@@ -0,0 +1,224 @@
+//! Blocking strategies, and how well blocking keys cover the rules.
+//!
+//! Blocking decides which record pairs are compared at all. A key that
+//! resolves to no source attribute blocks on nothing, and a rule whose field
+//! no key covers only ever sees pairs that agree on some other key.
+
+use serde::Serialize;
+
+use crate::diagnostic::{did_you_mean, Code, Diagnostic};
+use crate::rule_types::{check_params, Param, ParamKind, ParamProblem, PHONETIC_ALGORITHMS};
+use crate::spec::{Blocking, Spec, DEFAULT_BLOCKING_STRATEGY};
+
+/// A known blocking strategy.
+#[derive(Debug, Clone, Copy)]
+pub struct Strategy {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub params: &'static [Param],
+}
+
+pub const STRATEGIES: &[Strategy] = &[
+    Strategy {
+        name: "standard",
+        description: "Compare records that share a key value",
+        params: &[],
+    },
+    Strategy {
+        name: "sorted_neighborhood",
+        description: "Sort by the keys and compare records within `window`",
+        params: &[Param {
+            name: "window",
+            kind: ParamKind::Integer { min: 2 },
+            required: true,
+            default: None,
+        }],
+    },
+    Strategy {
+        name: "phonetic",
+        description: "Compare records whose keys sound alike",
+        params: &[Param {
+            name: "algorithm",
+            kind: ParamKind::OneOf(PHONETIC_ALGORITHMS),
+            required: false,
+            default: Some("soundex"),
+        }],
+    },
+    Strategy {
+        name: "lsh",
+        description: "Locality-sensitive hashing into `bands` of `rows`",
+        params: &[
+            Param {
+                name: "bands",
+                kind: ParamKind::Integer { min: 1 },
+                required: true,
+                default: None,
+            },
+            Param {
+                name: "rows",
+                kind: ParamKind::Integer { min: 1 },
+                required: true,
+                default: None,
+            },
+        ],
+    },
+];
+
+/// The strategy called `name`.
+pub fn lookup(name: &str) -> Option<&'static Strategy> {
+    STRATEGIES.iter().find(|strategy| strategy.name == name)
+}
+
+/// The strategy a blocking section uses, defaulting to `standard`.
+pub fn strategy_name(blocking: &Blocking) -> &str {
+    blocking
+        .strategy
+        .as_deref()
+        .unwrap_or(DEFAULT_BLOCKING_STRATEGY)
+}
+
+/// Check the strategy parameters, and that no key is a typo of a source
+/// attribute.
+pub fn check(spec: &Spec) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+    let Some(blocking) = &spec.blocking else {
+        return errors;
+    };
+
+    // Unknown strategies are rejected by the schema
+    if let Some(strategy) = lookup(strategy_name(blocking)) {
+        for problem in check_params(&blocking.params, strategy.params) {
+            errors.push(match problem {
+                ParamProblem::Missing(param) => Diagnostic::error(
+                    Code::InvalidBlockingParameter,
+                    "blocking",
+                    format!(
+                        "Blocking strategy '{}' is missing required parameter '{}'",
+                        strategy.name, param.name
+                    ),
+                )
+                .with_help(format!("Add '{}' to the blocking section", param.name)),
+                ParamProblem::Invalid(param, problem) => Diagnostic::error(
+                    Code::InvalidBlockingParameter,
+                    format!("blocking.{}", param.name),
+                    format!("Blocking parameter '{}' {}", param.name, problem),
+                ),
+            });
+        }
+    }
+
+    let available_fields = spec.available_fields();
+    if available_fields.is_empty() {
+        return errors;
+    }
+    for (i, key) in blocking.keys.iter().enumerate() {
+        if available_fields.contains(&key.as_str()) {
+            continue;
+        }
+        // A key no attribute resembles is the `unknown-blocking-key` lint
+        let Some(suggestion) = did_you_mean(key, available_fields.iter().copied()) else {
+            continue;
+        };
+        errors.push(
+            Diagnostic::error(
+                Code::UnresolvedBlockingKey,
+                format!("blocking.keys[{}]", i),
+                format!("Blocking key '{}' is not an attribute of any source", key),
+            )
+            .with_help(format!("Did you mean '{}'?", suggestion)),
+        );
+    }
+
+    errors
+}
+
+/// A source column a blocking key resolves to.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceColumn {
+    pub source: String,
+    pub column: String,
+}
+
+/// Which sources provide a blocking key, and under which column.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyCoverage {
+    pub key: String,
+    pub columns: Vec<SourceColumn>,
+    /// Sources that do not map the key; their records are never blocked on it
+    pub missing_in: Vec<String>,
+}
+
+/// Whether a blocking key covers the field a rule compares.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleCoverage {
+    pub rule: String,
+    pub field: Option<String>,
+    pub covered: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Coverage {
+    /// `None` when the spec has no blocking section
+    pub strategy: Option<String>,
+    pub keys: Vec<KeyCoverage>,
+    pub rules: Vec<RuleCoverage>,
+}
+
+impl Coverage {
+    /// Rules no blocking key covers.
+    pub fn uncovered(&self) -> impl Iterator<Item = &RuleCoverage> {
+        self.rules.iter().filter(|rule| !rule.covered)
+    }
+}
+
+/// Resolve each blocking key to source columns and check which rules the
+/// keys cover.
+pub fn coverage(spec: &Spec) -> Coverage {
+    let keys: &[String] = spec
+        .blocking
+        .as_ref()
+        .map(|b| b.keys.as_slice())
+        .unwrap_or_default();
+
+    let keys = keys
+        .iter()
+        .map(|key| {
+            let mut columns = Vec::new();
+            let mut missing_in = Vec::new();
+            for source in &spec.sources {
+                let name = source.name.clone().unwrap_or_default();
+                match source.attributes.as_ref().and_then(|a| a.get(key)) {
+                    Some(column) => columns.push(SourceColumn {
+                        source: name,
+                        column: column.clone(),
+                    }),
+                    None => missing_in.push(name),
+                }
+            }
+            KeyCoverage {
+                key: key.clone(),
+                columns,
+                missing_in,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let rules = spec
+        .rules
+        .iter()
+        .map(|rule| RuleCoverage {
+            rule: rule.name.clone().unwrap_or_default(),
+            field: rule.field.clone(),
+            covered: rule
+                .field
+                .as_ref()
+                .is_some_and(|field| keys.iter().any(|k| &k.key == field)),
+        })
+        .collect();
+
+    Coverage {
+        strategy: spec.blocking.as_ref().map(|b| strategy_name(b).to_string()),
+        keys,
+        rules,
+    }
+}
@@ -1,33 +1,39 @@
-use anyhow::{Context, Result};
-use sha2::{Digest, Sha256};
+use anyhow::{anyhow, Context, Result};
 use std::fs;
 use std::path::Path;
 
+use crate::commands::validate;
+use crate::hashing;
+use crate::ir::{self, Plan};
 use crate::parser;
+use crate::spec::Spec;
 
 pub fn run(file: &Path, output: Option<&Path>) -> Result<()> {
     // Read file
     let content = fs::read_to_string(file)
         .with_context(|| format!("Failed to read file: {}", file.display()))?;
 
+    // Only valid specs compile; the IR has no room for unresolved references
+    let report = validate::check(&file.display().to_string(), &content);
+    if !report.valid {
+        for error in &report.errors {
+            eprintln!("  → {}", error);
+        }
+        return Err(anyhow!(
+            "Cannot compile {}: {} validation error(s)",
+            file.display(),
+            report.errors.len()
+        ));
+    }
+
     // Parse YAML
     let spec = parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?;
 
-    // Compile to IR
-    let ir = compile_to_ir(&spec)?;
-
-    // Compute plan hash
-    let canonical_json = serde_json::to_string(&ir)?;
-    let mut hasher = Sha256::new();
-    hasher.update(canonical_json.as_bytes());
-    let hash = format!("sha256:{:x}", hasher.finalize());
-
-    // Add hash to IR
-    let mut ir_with_hash = ir;
-    ir_with_hash["plan_hash"] = serde_json::Value::String(hash);
+    // Compile to IR (hashed)
+    let plan = compile_to_ir(&spec)?;
 
     // Output
-    let output_json = serde_json::to_string_pretty(&ir_with_hash)?;
+    let output_json = serde_json::to_string_pretty(&plan)?;
 
     if let Some(output_path) = output {
         fs::write(output_path, &output_json)?;
@@ -39,38 +45,9 @@ pub fn run(file: &Path, output: Option<&Path>) -> Result<()> {
     Ok(())
 }
 
-pub fn compile_to_ir(spec: &serde_json::Value) -> Result<serde_json::Value> {
-    use sha2::{Digest, Sha256};
-
-    // Basic IR compilation - extracts key fields and normalizes structure
-    let ir = serde_json::json!({
-        "api_version": spec.get("api_version"),
-        "identity_version": spec.get("identity_version"),
-        "entity": spec.get("entity").and_then(|e| e.get("name")),
-        "sources": spec.get("sources").map(|s| {
-            s.as_array().map(|arr| {
-                arr.iter().map(|source| {
-                    serde_json::json!({
-                        "name": source.get("name"),
-                        "system": source.get("system"),
-                        "table": source.get("table"),
-                    })
-                }).collect::<Vec<_>>()
-            })
-        }),
-        "rule_count": spec.get("rules").and_then(|r| r.as_array()).map(|a| a.len()),
-        "blocking_strategy": spec.get("blocking").and_then(|b| b.get("strategy")),
-        "thresholds": spec.get("decision").and_then(|d| d.get("thresholds")),
-    });
-
-    // Compute plan hash
-    let canonical_json = serde_json::to_string(&ir)?;
-    let mut hasher = Sha256::new();
-    hasher.update(canonical_json.as_bytes());
-    let hash = format!("sha256:{:x}", hasher.finalize());
-
-    let mut ir_with_hash = ir;
-    ir_with_hash["plan_hash"] = serde_json::Value::String(hash);
-
-    Ok(ir_with_hash)
+/// Compile a spec into an executable [`Plan`] with every default resolved.
+pub fn compile_to_ir(spec: &Spec) -> Result<Plan> {
+    let mut plan = ir::lower(spec)?;
+    hashing::hash_plan(&mut plan)?;
+    Ok(plan)
 }
@@ -0,0 +1,86 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+
+use crate::blocking::{self, Coverage};
+use crate::parser;
+
+/// Report which sources provide each blocking key and which rules the keys
+/// cover.
+pub fn run(spec_file: &Path, format: &str) -> Result<()> {
+    let content = fs::read_to_string(spec_file)
+        .with_context(|| format!("Failed to read file: {}", spec_file.display()))?;
+    let spec = parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?;
+
+    let coverage = blocking::coverage(&spec);
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&coverage)?);
+    } else {
+        print_text(&coverage);
+    }
+
+    Ok(())
+}
+
+fn print_text(coverage: &Coverage) {
+    if coverage.keys.is_empty() {
+        println!(
+            "{} No blocking keys: every record is compared with every other",
+            "!".yellow().bold()
+        );
+    } else {
+        let keys: Vec<&str> = coverage.keys.iter().map(|k| k.key.as_str()).collect();
+        println!(
+            "{} {} on {}",
+            "Blocking:".bold(),
+            coverage.strategy.as_deref().unwrap_or_default(),
+            keys.join(", ")
+        );
+        for key in &coverage.keys {
+            let columns: Vec<String> = key
+                .columns
+                .iter()
+                .map(|c| format!("{}.{}", c.source, c.column))
+                .collect();
+            let columns = if columns.is_empty() {
+                "(no source)".red().to_string()
+            } else {
+                columns.join(", ")
+            };
+            print!("  {} → {}", key.key, columns);
+            if !key.missing_in.is_empty() && !key.columns.is_empty() {
+                print!(
+                    " {}",
+                    format!("(not mapped in: {})", key.missing_in.join(", ")).yellow()
+                );
+            }
+            println!();
+        }
+    }
+
+    println!();
+    println!("{}", "Rules:".bold());
+    for rule in &coverage.rules {
+        let field = rule.field.as_deref().unwrap_or("?");
+        if rule.covered {
+            println!("  {} {} on {}", "✓".green(), rule.rule, field);
+        } else {
+            println!(
+                "  {} {} on {}: no blocking key covers it",
+                "✗".red(),
+                rule.rule,
+                field
+            );
+        }
+    }
+
+    let uncovered = coverage.uncovered().count();
+    println!();
+    println!(
+        "{} of {} rule(s) compare a blocked field",
+        coverage.rules.len() - uncovered,
+        coverage.rules.len()
+    );
+}
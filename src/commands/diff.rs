@@ -1,138 +1,1188 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use crate::commands::validate::print_diagnostics;
+use crate::git;
+use crate::hashing;
+use crate::parser;
+use crate::patch;
+use crate::spec::{Rule, Source, Spec, DEFAULT_AGGREGATION, DEFAULT_BLOCKING_STRATEGY};
+use crate::versioning::{self, VersionScheme};
+
+/// Semantic differences between two versions of a spec.
+///
+/// Values are rendered as text: strings as-is, numbers and lists as JSON,
+/// and an unset value as the empty string. Where the model has a default
+/// (weights, thresholds, blocking strategy, aggregation) the effective value
+/// is compared, so writing a default out explicitly is not a change.
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct DiffResult {
+    /// `api_version`, `identity_version`, `entity.*` and other top-level keys
+    #[serde(default)]
+    pub spec_changes: Vec<FieldChange>,
+    #[serde(default)]
+    pub sources_added: Vec<String>,
+    #[serde(default)]
+    pub sources_removed: Vec<String>,
+    #[serde(default)]
+    pub sources_modified: Vec<SourceChange>,
+    #[serde(default)]
+    pub sources_renamed: Vec<Rename>,
     pub rules_added: Vec<String>,
     pub rules_removed: Vec<String>,
     pub rules_modified: Vec<RuleChange>,
+    /// Rules whose name changed but whose content (mostly) did not; any
+    /// remaining differences are listed in `rules_modified` under the new name
+    #[serde(default)]
+    pub rules_renamed: Vec<Rename>,
+    #[serde(default)]
+    pub blocking_changes: Vec<FieldChange>,
+    /// `aggregation` and each of `thresholds.match`/`review`/`reject`
+    #[serde(default)]
+    pub decision_changes: Vec<FieldChange>,
     pub thresholds_changed: bool,
+    /// Every change above, flattened and labeled with its impact
+    #[serde(default)]
+    pub changes: Vec<Change>,
     pub summary: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RuleChange {
     pub name: String,
-    pub field: String, // "weight", "threshold", etc.
+    #[serde(flatten)]
+    pub change: FieldChange, // "weight", "threshold", etc.
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceChange {
+    pub name: String,
+    #[serde(flatten)]
+    pub change: FieldChange, // "table", "attributes.email", etc.
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rename {
+    pub old_name: String,
+    pub new_name: String,
+    /// Share of compared properties that are equal, in [0, 1]
+    pub similarity: f64,
+}
+
+/// Similarity a removed/added pair needs to be reported as a rename.
+pub const DEFAULT_RENAME_THRESHOLD: f64 = 0.8;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DiffOptions {
+    /// Minimum similarity for rename detection; above 1.0 disables it
+    pub rename_threshold: f64,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            rename_threshold: DEFAULT_RENAME_THRESHOLD,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
     pub old_value: String,
     pub new_value: String,
 }
 
-pub fn run(file1: &Path, file2: &Path) -> Result<()> {
+/// Where the two versions of a spec come from.
+pub enum DiffSource<'a> {
+    /// Two files on disk
+    Files(&'a Path, &'a Path),
+    /// A file on disk against its content at a git revision
+    Against(&'a Path, &'a str),
+    /// Every spec under some paths that changed in a git range
+    GitRange(&'a str, &'a [PathBuf]),
+}
+
+/// Two versions of one spec; `None` when the spec does not exist on a side.
+struct Pair {
+    old_label: String,
+    old: Option<String>,
+    new_label: String,
+    new: Option<String>,
+}
+
+/// Print the diff; with `fail_on`, fail when any change reaches that impact,
+/// and with `require_bump`, when a changed plan keeps its `identity_version`.
+pub fn run(
+    source: DiffSource,
+    format: &str,
+    fail_on: Option<Impact>,
+    require_bump: Option<VersionScheme>,
+    options: &DiffOptions,
+) -> Result<()> {
+    let (pairs, multiple) = match source {
+        DiffSource::Files(file1, file2) => (vec![read_files(file1, file2)?], false),
+        DiffSource::Against(file, rev) => (vec![read_against(file, rev)?], false),
+        DiffSource::GitRange(range, paths) => (read_range(range, paths)?, true),
+    };
+
+    let mut reports = Vec::new();
+    let mut failing = 0;
+    let mut not_bumped = 0;
+    for pair in &pairs {
+        // The unified view is a plain text diff and works even on invalid specs
+        if format == "unified" {
+            print!("{}", render_unified(pair));
+        }
+        if format == "json-patch" {
+            let patch = patch_between(pair)?;
+            if multiple {
+                reports.push(serde_json::json!({
+                    "old": pair.old_label,
+                    "new": pair.new_label,
+                    "status": status(pair),
+                    "patch": patch,
+                }));
+            } else {
+                println!("{}", serde_json::to_string_pretty(&patch)?);
+            }
+        }
+
+        let diff = match (&pair.old, &pair.new) {
+            (Some(old), Some(new)) => Some(compute_diff_with(old, new, options)?),
+            _ => None,
+        };
+        if let Some(level) = fail_on {
+            failing += match &diff {
+                Some(diff) => diff.changes.iter().filter(|c| c.impact >= level).count(),
+                // Deleting a whole spec is breaking; adding one is risky
+                None if pair.new.is_none() => usize::from(level <= Impact::Breaking),
+                None => usize::from(level <= Impact::Risky),
+            };
+        }
+
+        match format {
+            "json" if multiple => reports.push(serde_json::json!({
+                "old": pair.old_label,
+                "new": pair.new_label,
+                "status": status(pair),
+                "diff": diff,
+            })),
+            "json" => println!("{}", serde_json::to_string_pretty(&diff)?),
+            "markdown" => print!("{}", render_markdown(pair, diff.as_ref())),
+            "unified" | "json-patch" => {}
+            _ => print_text(pair, diff.as_ref()),
+        }
+
+        if let (Some(scheme), Some(old), Some(new)) = (require_bump, &pair.old, &pair.new) {
+            let errors = versioning::check_bump(old, new, scheme)?;
+            if !errors.is_empty() {
+                not_bumped += 1;
+                eprintln!(
+                    "{} {}: identity_version not bumped",
+                    "✗".red().bold(),
+                    pair.new_label
+                );
+                print_diagnostics(&pair.new_label, &errors);
+            }
+        }
+    }
+
+    if matches!(format, "json" | "json-patch") && multiple {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else if multiple && pairs.is_empty() && format != "unified" {
+        println!("No spec changes in range.");
+    }
+
+    if not_bumped > 0 {
+        return Err(anyhow!(
+            "{} spec(s) changed without an identity_version bump (--require-version-bump)",
+            not_bumped
+        ));
+    }
+
+    if let Some(level) = fail_on {
+        if failing > 0 {
+            return Err(anyhow!(
+                "{} change(s) at or above '{}' impact (--fail-on {})",
+                failing,
+                level,
+                level
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn read_files(file1: &Path, file2: &Path) -> Result<Pair> {
     // Read files
     let content1 = fs::read_to_string(file1)
         .with_context(|| format!("Failed to read file: {}", file1.display()))?;
     let content2 = fs::read_to_string(file2)
         .with_context(|| format!("Failed to read file: {}", file2.display()))?;
 
-    let diff = compute_diff(&content1, &content2)?;
+    Ok(Pair {
+        old_label: file1.display().to_string(),
+        old: Some(content1),
+        new_label: file2.display().to_string(),
+        new: Some(content2),
+    })
+}
+
+fn read_against(file: &Path, rev: &str) -> Result<Pair> {
+    let old = git::show(rev, file)?
+        .ok_or_else(|| anyhow!("{} does not exist at {}", file.display(), rev))?;
+    let new = fs::read_to_string(file)
+        .with_context(|| format!("Failed to read file: {}", file.display()))?;
+
+    Ok(Pair {
+        old_label: format!("{}@{}", file.display(), rev),
+        old: Some(old),
+        new_label: file.display().to_string(),
+        new: Some(new),
+    })
+}
+
+fn read_range(range: &str, paths: &[PathBuf]) -> Result<Vec<Pair>> {
+    let (base, head) = git::parse_range(range)?;
+    let default_paths = [PathBuf::from(".")];
+    let paths = if paths.is_empty() {
+        &default_paths[..]
+    } else {
+        paths
+    };
+
+    let mut pairs = Vec::new();
+    for file in git::changed_files(&base, head.as_deref(), paths)? {
+        let is_spec = matches!(
+            file.extension().and_then(|e| e.to_str()),
+            Some("yaml" | "yml")
+        );
+        if !is_spec {
+            continue;
+        }
+
+        let old = git::show(&base, &file)?;
+        let (new_label, new) = match &head {
+            Some(head) => (
+                format!("{}@{}", file.display(), head),
+                git::show(head, &file)?,
+            ),
+            None => (file.display().to_string(), fs::read_to_string(&file).ok()),
+        };
+        pairs.push(Pair {
+            old_label: format!("{}@{}", file.display(), short_rev(&base)),
+            old,
+            new_label,
+            new,
+        });
+    }
+    Ok(pairs)
+}
+
+/// Abbreviate full SHAs (such as a computed merge base) for labels.
+fn short_rev(rev: &str) -> &str {
+    if rev.len() == 40 && rev.chars().all(|c| c.is_ascii_hexdigit()) {
+        &rev[..12]
+    } else {
+        rev
+    }
+}
+
+/// RFC 6902 patch from the old to the new version; a spec missing on one
+/// side is the JSON `null` document.
+fn patch_between(pair: &Pair) -> Result<patch::Patch> {
+    match (&pair.old, &pair.new) {
+        (Some(old), Some(new)) => patch::diff(old, new),
+        (old, new) => {
+            let document = |content: &Option<String>| match content {
+                Some(content) => parser::parse_value(content),
+                None => Ok(Value::Null),
+            };
+            Ok(json_patch::diff(&document(old)?, &document(new)?))
+        }
+    }
+}
+
+fn status(pair: &Pair) -> &'static str {
+    match (&pair.old, &pair.new) {
+        (None, _) => "added",
+        (_, None) => "deleted",
+        _ => "modified",
+    }
+}
+
+/// What happened to a component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Renamed,
+    Modified,
+}
+
+/// The part of a spec a change belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Section {
+    Spec,
+    Source,
+    Rule,
+    Blocking,
+    Decision,
+}
+
+/// How much a change can affect identity resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Impact {
+    /// Documentation and labels only
+    Safe,
+    /// Shifts scores or candidate pairs; worth a look
+    Risky,
+    /// Changes which records merge, or what an entity is
+    Breaking,
+}
+
+impl Impact {
+    pub fn parse(name: &str) -> Option<Impact> {
+        match name {
+            "safe" => Some(Impact::Safe),
+            "risky" => Some(Impact::Risky),
+            "breaking" => Some(Impact::Breaking),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Impact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Impact::Safe => "safe",
+            Impact::Risky => "risky",
+            Impact::Breaking => "breaking",
+        })
+    }
+}
+
+/// One change of a [`DiffResult`] with its impact, in report order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub section: Section,
+    /// Source or rule name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The field that changed; `None` for additions and removals
+    #[serde(flatten)]
+    pub change: Option<FieldChange>,
+    /// Content similarity, for renames
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f64>,
+    pub impact: Impact,
+}
+
+impl DiffResult {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Highest impact among the changes, if there are any.
+    pub fn impact(&self) -> Option<Impact> {
+        self.changes.iter().map(|c| c.impact).max()
+    }
+
+    /// Flatten the per-section lists into [`DiffResult::changes`].
+    fn collect_changes(&mut self) {
+        let mut changes = Vec::new();
+        let mut push = |kind, section, name: Option<&String>, change: Option<FieldChange>| {
+            let mut change = Change {
+                kind,
+                section,
+                name: name.cloned(),
+                change,
+                similarity: None,
+                impact: Impact::Safe,
+            };
+            change.impact = classify(&change);
+            changes.push(change);
+        };
+        let modified = ChangeKind::Modified;
+
+        for c in &self.spec_changes {
+            push(modified, Section::Spec, None, Some(c.clone()));
+        }
+        for name in &self.sources_added {
+            push(ChangeKind::Added, Section::Source, Some(name), None);
+        }
+        for name in &self.sources_removed {
+            push(ChangeKind::Removed, Section::Source, Some(name), None);
+        }
+        for r in &self.sources_renamed {
+            push(
+                ChangeKind::Renamed,
+                Section::Source,
+                Some(&r.new_name),
+                Some(r.as_change()),
+            );
+        }
+        for c in &self.sources_modified {
+            push(
+                modified,
+                Section::Source,
+                Some(&c.name),
+                Some(c.change.clone()),
+            );
+        }
+        for name in &self.rules_added {
+            push(ChangeKind::Added, Section::Rule, Some(name), None);
+        }
+        for name in &self.rules_removed {
+            push(ChangeKind::Removed, Section::Rule, Some(name), None);
+        }
+        for r in &self.rules_renamed {
+            push(
+                ChangeKind::Renamed,
+                Section::Rule,
+                Some(&r.new_name),
+                Some(r.as_change()),
+            );
+        }
+        for c in &self.rules_modified {
+            push(
+                modified,
+                Section::Rule,
+                Some(&c.name),
+                Some(c.change.clone()),
+            );
+        }
+        for c in &self.blocking_changes {
+            push(modified, Section::Blocking, None, Some(c.clone()));
+        }
+        for c in &self.decision_changes {
+            push(modified, Section::Decision, None, Some(c.clone()));
+        }
+
+        // Renames carry their similarity score
+        let renames = self.sources_renamed.iter().chain(&self.rules_renamed);
+        let renamed = changes.iter_mut().filter(|c| c.kind == ChangeKind::Renamed);
+        for (change, rename) in renamed.zip(renames) {
+            change.similarity = Some(rename.similarity);
+        }
+        self.changes = changes;
+    }
+}
+
+impl Rename {
+    fn as_change(&self) -> FieldChange {
+        FieldChange {
+            field: "name".to_string(),
+            old_value: self.old_name.clone(),
+            new_value: self.new_name.clone(),
+        }
+    }
+}
+
+impl Change {
+    /// `rule email_exact`, `source crm`, `blocking`, ...
+    pub fn component(&self) -> String {
+        let section = match self.section {
+            Section::Spec => "spec",
+            Section::Source => "source",
+            Section::Rule => "rule",
+            Section::Blocking => "blocking",
+            Section::Decision => "decision",
+        };
+        match &self.name {
+            Some(name) => format!("{} {}", section, name),
+            None => section.to_string(),
+        }
+    }
+}
+
+/// Impact of a single change.
+///
+/// Breaking: the entity is renamed, a source is removed or re-keyed, the
+/// aggregation changes, or any threshold is lowered (more pairs merge).
+/// Safe: descriptions, metadata, `identity_version`, rule renames and newly
+/// mapped attributes. Everything else (weights, rule types and fields, raised
+/// thresholds, blocking, added or removed rules) is risky.
+pub fn classify(change: &Change) -> Impact {
+    let field = change.change.as_ref().map(|c| c.field.as_str());
+    let lowered = change.change.as_ref().is_some_and(|c| {
+        match (c.old_value.parse::<f64>(), c.new_value.parse::<f64>()) {
+            (Ok(old), Ok(new)) => new < old,
+            _ => false,
+        }
+    });
+    let documentation = field.is_some_and(|f| {
+        f == "description"
+            || f.ends_with(".description")
+            || f == "metadata"
+            || f.starts_with("metadata.")
+    });
+
+    match (change.section, change.kind, field) {
+        _ if documentation => Impact::Safe,
+        (Section::Spec, _, Some("identity_version")) => Impact::Safe,
+        (Section::Rule, ChangeKind::Renamed, _) => Impact::Safe,
+        (Section::Spec, _, Some("entity.name")) => Impact::Breaking,
+        (Section::Source, ChangeKind::Removed, _) => Impact::Breaking,
+        (Section::Source, _, Some("id")) => Impact::Breaking,
+        (Section::Source, _, Some(f)) if f.starts_with("attributes.") => {
+            let added = change
+                .change
+                .as_ref()
+                .is_some_and(|c| c.old_value.is_empty());
+            if added {
+                Impact::Safe
+            } else {
+                Impact::Risky
+            }
+        }
+        (Section::Decision, _, Some("aggregation")) => Impact::Breaking,
+        (Section::Decision, _, Some(f)) if f.starts_with("thresholds.") && lowered => {
+            Impact::Breaking
+        }
+        (Section::Rule, _, Some("threshold")) if lowered => Impact::Breaking,
+        _ => Impact::Risky,
+    }
+}
 
-    // Print readable output (preserving CLI behavior)
+fn print_text(pair: &Pair, diff: Option<&DiffResult>) {
     println!(
         "{} {} vs {}",
         "Comparing:".bold(),
-        file1.display(),
-        file2.display()
+        pair.old_label,
+        pair.new_label
     );
     println!();
 
-    if !diff.rules_added.is_empty() {
-        println!("{}:", "Rules Added".green());
-        for r in &diff.rules_added {
-            println!("  + {}", r);
-        }
-    }
+    let Some(diff) = diff else {
+        println!("{}", whole_spec_change(pair));
+        println!();
+        return;
+    };
 
-    if !diff.rules_removed.is_empty() {
-        println!("{}:", "Rules Removed".red());
-        for r in &diff.rules_removed {
-            println!("  - {}", r);
+    let mut heading = None;
+    for entry in &diff.changes {
+        let title = match (entry.section, entry.kind) {
+            (Section::Spec, _) => "Spec".yellow(),
+            (Section::Source, ChangeKind::Added) => "Sources Added".green(),
+            (Section::Source, ChangeKind::Removed) => "Sources Removed".red(),
+            (Section::Source, ChangeKind::Renamed) => "Sources Renamed".blue(),
+            (Section::Source, ChangeKind::Modified) => "Sources Modified".yellow(),
+            (Section::Rule, ChangeKind::Added) => "Rules Added".green(),
+            (Section::Rule, ChangeKind::Removed) => "Rules Removed".red(),
+            (Section::Rule, ChangeKind::Renamed) => "Rules Renamed".blue(),
+            (Section::Rule, ChangeKind::Modified) => "Rules Modified".yellow(),
+            (Section::Blocking, _) => "Blocking".yellow(),
+            (Section::Decision, _) => "Decision".cyan(),
+        };
+        if heading.as_ref() != Some(&title) {
+            println!("{}:", title);
+            heading = Some(title);
         }
-    }
 
-    if !diff.rules_modified.is_empty() {
-        println!("{}:", "Rules Modified".yellow());
-        for m in &diff.rules_modified {
-            println!("  ~ {} ({} changed from {} to {})", m.name, m.field, m.old_value, m.new_value);
-        }
+        let marker = match entry.kind {
+            ChangeKind::Added => "+",
+            ChangeKind::Removed => "-",
+            ChangeKind::Renamed => ">",
+            ChangeKind::Modified => "~",
+        };
+        let line = match (&entry.name, &entry.change) {
+            (Some(_), Some(c)) if entry.kind == ChangeKind::Renamed => format!(
+                "renamed: {} -> {} (similarity {:.2})",
+                c.old_value,
+                c.new_value,
+                entry.similarity.unwrap_or(1.0)
+            ),
+            (Some(name), Some(c)) => format!(
+                "{} ({} changed from {} to {})",
+                name,
+                c.field,
+                shown(&c.old_value),
+                shown(&c.new_value)
+            ),
+            (None, Some(c)) => format!(
+                "{} changed from {} to {}",
+                c.field,
+                shown(&c.old_value),
+                shown(&c.new_value)
+            ),
+            (Some(name), None) => name.clone(),
+            (None, None) => String::new(),
+        };
+        println!("  {} {} {}", marker, line, impact_label(entry.impact));
     }
 
     if diff.thresholds_changed {
-        println!("{}:", "Thresholds".cyan());
         println!("  {} Thresholds have changed.", "⚠".yellow());
     }
 
-    if diff.rules_added.is_empty() && diff.rules_removed.is_empty() && diff.rules_modified.is_empty() && !diff.thresholds_changed {
+    if diff.is_empty() {
         println!("No significant changes detected.");
     }
+}
 
-    Ok(())
+/// Text for a spec that only exists on one side.
+fn whole_spec_change(pair: &Pair) -> String {
+    match status(pair) {
+        "added" => format!("New spec (not present in {})", pair.old_label),
+        _ => format!("Spec deleted (not present in {})", pair.new_label),
+    }
 }
 
-pub fn compute_diff(content1: &str, content2: &str) -> Result<DiffResult> {
-    let spec1: serde_json::Value = serde_yaml::from_str(content1)?;
-    let spec2: serde_json::Value = serde_yaml::from_str(content2)?;
+fn impact_label(impact: Impact) -> colored::ColoredString {
+    let label = format!("[{}]", impact);
+    match impact {
+        Impact::Breaking => label.red().bold(),
+        Impact::Risky => label.yellow(),
+        Impact::Safe => label.dimmed(),
+    }
+}
 
-    let mut diff = DiffResult::default();
+fn shown(value: &str) -> &str {
+    if value.is_empty() {
+        "(unset)"
+    } else {
+        value
+    }
+}
 
-    // Compare identity versions (minor, included in summary)
-    let v1 = spec1.get("identity_version").and_then(|v| v.as_str()).unwrap_or("unknown");
-    let v2 = spec2.get("identity_version").and_then(|v| v.as_str()).unwrap_or("unknown");
+/// GitHub-flavored Markdown, ready to post as a PR comment.
+fn render_markdown(pair: &Pair, diff: Option<&DiffResult>) -> String {
+    let mut out = format!(
+        "### Identity spec diff: `{}` → `{}`\n\n",
+        pair.old_label, pair.new_label
+    );
 
-    // Compare rules
-    let rules1 = spec1.get("rules").and_then(|r| r.as_array());
-    let rules2 = spec2.get("rules").and_then(|r| r.as_array());
+    let Some(diff) = diff else {
+        out.push_str(&whole_spec_change(pair));
+        out.push_str("\n\n");
+        return out;
+    };
 
-    if let (Some(r1), Some(r2)) = (rules1, rules2) {
-        // Collect names
-        let names1: Vec<&str> = r1.iter().filter_map(|r| r.get("name").and_then(|n| n.as_str())).collect();
-        let names2: Vec<&str> = r2.iter().filter_map(|r| r.get("name").and_then(|n| n.as_str())).collect();
+    if diff.is_empty() {
+        out.push_str("No significant changes detected.\n");
+        return out;
+    }
 
-        for name in &names2 {
-            if !names1.contains(name) {
-                diff.rules_added.push(name.to_string());
-            } else {
-                // Check for modifications
-                let rule1 = r1.iter().find(|r| r.get("name").and_then(|n| n.as_str()) == Some(name)).unwrap();
-                let rule2 = r2.iter().find(|r| r.get("name").and_then(|n| n.as_str()) == Some(name)).unwrap();
+    if let Some(impact) = diff.impact() {
+        out.push_str(&format!("**Impact:** {}\n\n", impact));
+    }
 
-                // Compare weight
-                if rule1.get("weight") != rule2.get("weight") {
-                    diff.rules_modified.push(RuleChange {
+    out.push_str("| Impact | Change | Component | Details |\n|---|---|---|---|\n");
+    for entry in &diff.changes {
+        let kind = match entry.kind {
+            ChangeKind::Added => "Added",
+            ChangeKind::Removed => "Removed",
+            ChangeKind::Renamed => "Renamed",
+            ChangeKind::Modified => "Modified",
+        };
+        let details = entry
+            .change
+            .as_ref()
+            .map(|c| {
+                format!(
+                    "`{}`: {} → {}",
+                    c.field,
+                    markdown_value(&c.old_value),
+                    markdown_value(&c.new_value)
+                )
+            })
+            .unwrap_or_default();
+        let details = match entry.similarity {
+            Some(similarity) => format!("{} (similarity {:.2})", details, similarity),
+            None => details,
+        };
+        out.push_str(&format!(
+            "| {} | {} | `{}` | {} |\n",
+            entry.impact,
+            kind,
+            entry.component(),
+            details
+        ));
+    }
+    out.push('\n');
+
+    if diff.thresholds_changed {
+        out.push_str("> **Warning:** decision thresholds changed; match rates may shift.\n\n");
+    }
+
+    out.push_str(&format!("_{}_\n", diff.summary));
+    out
+}
+
+fn markdown_value(value: &str) -> String {
+    if value.is_empty() {
+        "_unset_".to_string()
+    } else {
+        format!("`{}`", value.replace('|', "\\|"))
+    }
+}
+
+/// Line diff of the two YAML documents in unified format.
+fn render_unified(pair: &Pair) -> String {
+    let lines1: Vec<&str> = pair.old.as_deref().unwrap_or_default().lines().collect();
+    let lines2: Vec<&str> = pair.new.as_deref().unwrap_or_default().lines().collect();
+    let from = pair.old_label.as_str();
+    let to = pair.new_label.as_str();
+
+    let mut out = String::new();
+    for line in difflib::unified_diff(&lines1, &lines2, from, to, "", "", 3) {
+        let line = line.trim_end_matches('\n');
+        let colored = if line.starts_with("+++") || line.starts_with("---") {
+            // difflib leaves a tab before the (empty) timestamp
+            line.trim_end().bold().to_string()
+        } else if line.starts_with("@@") {
+            line.cyan().to_string()
+        } else if line.starts_with('+') {
+            line.green().to_string()
+        } else if line.starts_with('-') {
+            line.red().to_string()
+        } else {
+            line.to_string()
+        };
+        out.push_str(&colored);
+        out.push('\n');
+    }
+    out
+}
+
+pub fn compute_diff(content1: &str, content2: &str) -> Result<DiffResult> {
+    compute_diff_with(content1, content2, &DiffOptions::default())
+}
+
+pub fn compute_diff_with(
+    content1: &str,
+    content2: &str,
+    options: &DiffOptions,
+) -> Result<DiffResult> {
+    let spec1 = parser::parse_yaml(content1)?;
+    let spec2 = parser::parse_yaml(content2)?;
+
+    let mut diff = DiffResult {
+        spec_changes: spec_changes(&spec1, &spec2),
+        blocking_changes: blocking_changes(&spec1, &spec2),
+        decision_changes: decision_changes(&spec1, &spec2),
+        ..DiffResult::default()
+    };
+    diff.thresholds_changed = diff
+        .decision_changes
+        .iter()
+        .any(|c| c.field.starts_with("thresholds."));
+
+    // Compare sources
+    let sources1: Vec<&str> = spec1
+        .sources
+        .iter()
+        .filter_map(|s| s.name.as_deref())
+        .collect();
+    let sources2: Vec<&str> = spec2
+        .sources
+        .iter()
+        .filter_map(|s| s.name.as_deref())
+        .collect();
+
+    for name in &sources2 {
+        match spec1.source(name) {
+            None => diff.sources_added.push(name.to_string()),
+            Some(old) => {
+                let new = spec2.source(name).expect("name comes from spec2");
+                for change in field_changes(source_fields(old, new)) {
+                    diff.sources_modified.push(SourceChange {
                         name: name.to_string(),
-                        field: "weight".to_string(),
-                        old_value: rule1.get("weight").map(|v| v.to_string()).unwrap_or_default(),
-                        new_value: rule2.get("weight").map(|v| v.to_string()).unwrap_or_default(),
+                        change,
                     });
                 }
             }
         }
+    }
+    for name in &sources1 {
+        if !sources2.contains(name) {
+            diff.sources_removed.push(name.to_string());
+        }
+    }
+    let source_pair = |old: &str, new: &str| {
+        let (old, new) = (spec1.source(old)?, spec2.source(new)?);
+        Some((source_fields(old, new), source_field_count(old, new)))
+    };
+    for (rename, changes) in detect_renames(
+        &mut diff.sources_removed,
+        &mut diff.sources_added,
+        source_pair,
+        options.rename_threshold,
+    ) {
+        for change in changes {
+            diff.sources_modified.push(SourceChange {
+                name: rename.new_name.clone(),
+                change,
+            });
+        }
+        diff.sources_renamed.push(rename);
+    }
+
+    // Compare rules
+    let names1: Vec<&str> = spec1
+        .rules
+        .iter()
+        .filter_map(|r| r.name.as_deref())
+        .collect();
+    let names2: Vec<&str> = spec2
+        .rules
+        .iter()
+        .filter_map(|r| r.name.as_deref())
+        .collect();
 
-        for name in &names1 {
-            if !names2.contains(name) {
-                diff.rules_removed.push(name.to_string());
+    for name in &names2 {
+        match spec1.rule(name) {
+            None => diff.rules_added.push(name.to_string()),
+            Some(old) => {
+                let new = spec2.rule(name).expect("name comes from spec2");
+                for change in field_changes(rule_fields(old, new)) {
+                    diff.rules_modified.push(RuleChange {
+                        name: name.to_string(),
+                        change,
+                    });
+                }
             }
         }
     }
-
-    // Compare thresholds
-    let t1 = spec1.get("decision").and_then(|d| d.get("thresholds"));
-    let t2 = spec2.get("decision").and_then(|d| d.get("thresholds"));
-    if t1 != t2 {
-        diff.thresholds_changed = true;
+    for name in &names1 {
+        if !names2.contains(name) {
+            diff.rules_removed.push(name.to_string());
+        }
+    }
+    let rule_pair = |old: &str, new: &str| {
+        let (old, new) = (spec1.rule(old)?, spec2.rule(new)?);
+        Some((rule_fields(old, new), rule_field_count(old, new)))
+    };
+    for (rename, changes) in detect_renames(
+        &mut diff.rules_removed,
+        &mut diff.rules_added,
+        rule_pair,
+        options.rename_threshold,
+    ) {
+        for change in changes {
+            diff.rules_modified.push(RuleChange {
+                name: rename.new_name.clone(),
+                change,
+            });
+        }
+        diff.rules_renamed.push(rename);
     }
 
+    diff.collect_changes();
+
+    let v1 = spec1.identity_version.as_deref().unwrap_or("unknown");
+    let v2 = spec2.identity_version.as_deref().unwrap_or("unknown");
     diff.summary = format!(
-        "Diff: {} added, {} removed, {} modified. Thresholds changed: {}. Version: {} -> {}",
-        diff.rules_added.len(),
-        diff.rules_removed.len(),
-        diff.rules_modified.len(),
+        "Diff: {} added, {} removed, {} renamed, {} modified. Thresholds changed: {}. Version: {} -> {}",
+        diff.rules_added.len() + diff.sources_added.len(),
+        diff.rules_removed.len() + diff.sources_removed.len(),
+        diff.rules_renamed.len() + diff.sources_renamed.len(),
+        diff.rules_modified.len()
+            + diff.sources_modified.len()
+            + diff.spec_changes.len()
+            + diff.blocking_changes.len()
+            + diff.decision_changes.len(),
         diff.thresholds_changed,
-        v1, v2
+        v1,
+        v2
     );
 
     Ok(diff)
 }
+
+/// `(field, old, new)` for every field that differs.
+type Changes = Vec<(String, String, String)>;
+
+/// Pair removed and added names whose content is similar enough, best
+/// matches first, taking them out of `removed`/`added`.
+///
+/// `compare(old, new)` returns the differing fields and how many fields
+/// were compared; similarity is the share of fields that are equal.
+fn detect_renames(
+    removed: &mut Vec<String>,
+    added: &mut Vec<String>,
+    compare: impl Fn(&str, &str) -> Option<(Changes, usize)>,
+    threshold: f64,
+) -> Vec<(Rename, Vec<FieldChange>)> {
+    let mut candidates = Vec::new();
+    for old in removed.iter() {
+        for new in added.iter() {
+            let Some((changes, total)) = compare(old, new) else {
+                continue;
+            };
+            let similarity = if total == 0 {
+                1.0
+            } else {
+                1.0 - changes.len() as f64 / total as f64
+            };
+            if similarity >= threshold {
+                candidates.push((similarity, old.clone(), new.clone(), changes));
+            }
+        }
+    }
+    // Stable sort keeps spec order among equally similar pairs
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut renames = Vec::new();
+    for (similarity, old, new, changes) in candidates {
+        if !removed.contains(&old) || !added.contains(&new) {
+            continue;
+        }
+        removed.retain(|n| *n != old);
+        added.retain(|n| *n != new);
+        renames.push((
+            Rename {
+                old_name: old,
+                new_name: new,
+                similarity,
+            },
+            field_changes(changes),
+        ));
+    }
+    renames
+}
+
+fn spec_changes(spec1: &Spec, spec2: &Spec) -> Vec<FieldChange> {
+    let mut changes = Changes::new();
+    compare(
+        &mut changes,
+        "api_version",
+        &spec1.api_version,
+        &spec2.api_version,
+    );
+    compare(
+        &mut changes,
+        "identity_version",
+        &spec1.identity_version,
+        &spec2.identity_version,
+    );
+    compare(
+        &mut changes,
+        "entity.name",
+        &spec1.entity_name(),
+        &spec2.entity_name(),
+    );
+
+    let entity_extra = |spec: &Spec| {
+        spec.entity
+            .as_ref()
+            .map(|e| e.extra.clone())
+            .unwrap_or_default()
+    };
+    compare_maps(
+        &mut changes,
+        "entity.",
+        &entity_extra(spec1),
+        &entity_extra(spec2),
+    );
+    compare_maps(&mut changes, "", &spec1.extra, &spec2.extra);
+    field_changes(changes)
+}
+
+fn blocking_changes(spec1: &Spec, spec2: &Spec) -> Vec<FieldChange> {
+    let blocking1 = spec1.blocking.clone().unwrap_or_default();
+    let blocking2 = spec2.blocking.clone().unwrap_or_default();
+    let strategy = |b: &crate::spec::Blocking| {
+        b.strategy
+            .clone()
+            .unwrap_or_else(|| DEFAULT_BLOCKING_STRATEGY.to_string())
+    };
+
+    let mut changes = Changes::new();
+    compare(
+        &mut changes,
+        "strategy",
+        &strategy(&blocking1),
+        &strategy(&blocking2),
+    );
+    // Keys are a set, as in the plan hash
+    let keys = |b: &crate::spec::Blocking| {
+        b.keys
+            .iter()
+            .cloned()
+            .collect::<std::collections::BTreeSet<_>>()
+    };
+    compare(&mut changes, "keys", &keys(&blocking1), &keys(&blocking2));
+    compare_maps(
+        &mut changes,
+        "",
+        &blocking1.params_or_default(),
+        &blocking2.params_or_default(),
+    );
+    field_changes(changes)
+}
+
+fn decision_changes(spec1: &Spec, spec2: &Spec) -> Vec<FieldChange> {
+    let aggregation = |spec: &Spec| {
+        spec.decision
+            .as_ref()
+            .and_then(|d| d.aggregation.clone())
+            .unwrap_or_else(|| DEFAULT_AGGREGATION.to_string())
+    };
+    let t1 = spec1.thresholds().copied().unwrap_or_default();
+    let t2 = spec2.thresholds().copied().unwrap_or_default();
+    let extra = |spec: &Spec| {
+        spec.decision
+            .as_ref()
+            .map(|d| d.extra.clone())
+            .unwrap_or_default()
+    };
+
+    let mut changes = Changes::new();
+    compare(
+        &mut changes,
+        "aggregation",
+        &aggregation(spec1),
+        &aggregation(spec2),
+    );
+    compare(
+        &mut changes,
+        "thresholds.match",
+        &t1.match_or_default(),
+        &t2.match_or_default(),
+    );
+    compare(
+        &mut changes,
+        "thresholds.review",
+        &t1.review_or_default(),
+        &t2.review_or_default(),
+    );
+    compare(
+        &mut changes,
+        "thresholds.reject",
+        &t1.reject_or_default(),
+        &t2.reject_or_default(),
+    );
+    compare_maps(&mut changes, "", &extra(spec1), &extra(spec2));
+    field_changes(changes)
+}
+
+fn source_fields(old: &Source, new: &Source) -> Changes {
+    let mut changes = Changes::new();
+    compare(&mut changes, "system", &old.system, &new.system);
+    compare(&mut changes, "table", &old.table, &new.table);
+    compare(&mut changes, "id", &old.id, &new.id);
+
+    let attributes = |source: &Source| -> BTreeMap<String, Value> {
+        source
+            .attributes
+            .iter()
+            .flatten()
+            .map(|(k, v)| (k.clone(), Value::from(v.as_str())))
+            .collect()
+    };
+    compare_maps(
+        &mut changes,
+        "attributes.",
+        &attributes(old),
+        &attributes(new),
+    );
+    compare_maps(&mut changes, "", &old.extra, &new.extra);
+    changes
+}
+
+/// Number of fields [`source_fields`] compares.
+fn source_field_count(old: &Source, new: &Source) -> usize {
+    let attributes: std::collections::BTreeSet<&String> = old
+        .attributes
+        .iter()
+        .chain(new.attributes.iter())
+        .flat_map(|a| a.keys())
+        .collect();
+    let extra: std::collections::BTreeSet<&String> =
+        old.extra.keys().chain(new.extra.keys()).collect();
+    3 + attributes.len() + extra.len()
+}
+
+/// Number of fields [`rule_fields`] compares.
+fn rule_field_count(old: &Rule, new: &Rule) -> usize {
+    let params: std::collections::BTreeSet<&String> =
+        old.params.keys().chain(new.params.keys()).collect();
+    4 + params.len()
+}
+
+fn rule_fields(old: &Rule, new: &Rule) -> Changes {
+    let mut changes = Changes::new();
+    compare(&mut changes, "type", &old.rule_type, &new.rule_type);
+    compare(&mut changes, "field", &old.field, &new.field);
+    compare(
+        &mut changes,
+        "weight",
+        &old.weight_or_default(),
+        &new.weight_or_default(),
+    );
+    compare(
+        &mut changes,
+        "threshold",
+        &old.threshold_or_default(),
+        &new.threshold_or_default(),
+    );
+    compare_maps(
+        &mut changes,
+        "",
+        &old.params_or_default(),
+        &new.params_or_default(),
+    );
+    changes
+}
+
+/// Record `field` if `old` and `new` differ. Values are compared in their
+/// canonical form, as the plan hash sees them, so `2` and `2.0` are equal.
+fn compare<T: Serialize>(changes: &mut Changes, field: &str, old: &T, new: &T) {
+    let old = serde_json::to_value(old).unwrap_or_default();
+    let new = serde_json::to_value(new).unwrap_or_default();
+    if hashing::canonicalize(&old) != hashing::canonicalize(&new) {
+        changes.push((field.to_string(), render(&old), render(&new)));
+    }
+}
+
+/// Compare two maps key by key; `prefix` is prepended to each field name.
+fn compare_maps(
+    changes: &mut Changes,
+    prefix: &str,
+    old: &BTreeMap<String, Value>,
+    new: &BTreeMap<String, Value>,
+) {
+    let keys: std::collections::BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        compare(
+            changes,
+            &format!("{}{}", prefix, key),
+            &old.get(key),
+            &new.get(key),
+        );
+    }
+}
+
+fn field_changes(changes: Changes) -> Vec<FieldChange> {
+    changes
+        .into_iter()
+        .map(|(field, old_value, new_value)| FieldChange {
+            field,
+            old_value,
+            new_value,
+        })
+        .collect()
+}
+
+/// Text form of a value: strings unquoted, unset as empty, the rest as JSON.
+fn render(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
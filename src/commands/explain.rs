@@ -0,0 +1,114 @@
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::parser;
+use crate::scoring::{self, Band, Explanation};
+
+pub fn run(spec_file: &Path, records_file: &Path, format: &str) -> Result<()> {
+    // Read files
+    let content = fs::read_to_string(spec_file)
+        .with_context(|| format!("Failed to read file: {}", spec_file.display()))?;
+    let records = fs::read_to_string(records_file)
+        .with_context(|| format!("Failed to read file: {}", records_file.display()))?;
+
+    let spec = parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?;
+    let records: Value = serde_json::from_str(&records)
+        .with_context(|| format!("Failed to parse JSON: {}", records_file.display()))?;
+    let (left, right) = record_pair(&records)?;
+
+    let explanation = scoring::explain(&spec, left, right);
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&explanation)?);
+    } else {
+        print_text(&explanation);
+    }
+
+    Ok(())
+}
+
+type Record = Map<String, Value>;
+
+/// Accept either `{"left": {...}, "right": {...}}` or `[{...}, {...}]`.
+fn record_pair(records: &Value) -> Result<(&Record, &Record)> {
+    let (left, right) = match records {
+        Value::Object(pair) => (pair.get("left"), pair.get("right")),
+        Value::Array(pair) if pair.len() == 2 => (pair.first(), pair.get(1)),
+        _ => (None, None),
+    };
+    match (left.and_then(|l| l.as_object()), right.and_then(|r| r.as_object())) {
+        (Some(left), Some(right)) => Ok((left, right)),
+        _ => Err(anyhow!(
+            "Expected a record pair: {{\"left\": {{...}}, \"right\": {{...}}}} or [{{...}}, {{...}}]"
+        )),
+    }
+}
+
+fn print_text(explanation: &Explanation) {
+    println!("{}", "Rules:".bold());
+    for outcome in &explanation.rules {
+        let marker = if outcome.passed {
+            "✓".green()
+        } else {
+            "✗".red()
+        };
+        let similarity = outcome
+            .similarity
+            .map(|s| format!("{:.3}", s))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "  {} {} ({}) on {}: similarity {} vs threshold {:.2} → +{:.3} (weight {:.2})",
+            marker,
+            outcome.rule,
+            outcome.rule_type,
+            outcome.field.as_deref().unwrap_or("?"),
+            similarity,
+            outcome.threshold,
+            outcome.contribution,
+            outcome.weight
+        );
+        println!(
+            "      {} vs {}",
+            display_value(outcome.left.as_ref()),
+            display_value(outcome.right.as_ref())
+        );
+        if let Some(note) = &outcome.note {
+            println!("      {}", note.dimmed());
+        }
+    }
+
+    println!();
+    println!(
+        "{} {:.3} ({})",
+        "Score:".bold(),
+        explanation.score,
+        explanation.aggregation
+    );
+    if let Some(t) = &explanation.thresholds {
+        let fmt = |v: Option<f64>| v.map(|v| format!("{:.2}", v)).unwrap_or_else(|| "-".into());
+        println!(
+            "{} match >= {}, review >= {}, reject >= {}",
+            "Thresholds:".bold(),
+            fmt(t.match_),
+            fmt(t.review),
+            fmt(t.reject)
+        );
+    }
+    let band = match explanation.band {
+        Band::Match => "MATCH".green().bold(),
+        Band::Review => "REVIEW".yellow().bold(),
+        Band::Reject => "REJECT".red().bold(),
+    };
+    println!("{} {}", "Decision:".bold(), band);
+}
+
+fn display_value(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => format!("{:?}", s),
+        Some(other) => other.to_string(),
+        None => "(missing)".to_string(),
+    }
+}
@@ -1,26 +1,145 @@
-use anyhow::{Context, Result};
-use sha2::{Digest, Sha256};
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-pub fn run(file: &Path) -> Result<()> {
+use crate::commands::compile;
+use crate::hashing;
+use crate::lockfile::Lockfile;
+use crate::parser;
+
+pub struct HashOptions<'a> {
+    /// Hash the document byte-for-byte as parsed instead of the plan
+    pub strict: bool,
+    /// Also print the hash of every component under the plan hash
+    pub tree: bool,
+    /// Fail unless the (single) spec hashes to this value
+    pub check: Option<&'a str>,
+    pub lockfile: &'a Path,
+    /// Fail unless every spec matches its entry in `lockfile`
+    pub locked: bool,
+    /// Record the hash of every spec in `lockfile`
+    pub update_lock: bool,
+}
+
+/// Print the plan hash: the same value `compile` writes to `plan_hash`.
+///
+/// With several files each hash is followed by its path. `check`, `locked`
+/// and `update_lock` compare against or record expected hashes instead.
+pub fn run(files: &[PathBuf], options: &HashOptions) -> Result<()> {
+    if options.check.is_some() && files.len() != 1 {
+        return Err(anyhow!("--check takes exactly one spec file"));
+    }
+
+    let mut lock = if options.locked || options.update_lock {
+        Lockfile::load(options.lockfile)?
+    } else {
+        Lockfile::default()
+    };
+
+    let mut mismatches = 0;
+    for file in files {
+        let (hash, components) = hash_file(file, options.strict)?;
+
+        if let Some(expected) = options.check {
+            mismatches += usize::from(!report_check(file, &hash, Some(expected)));
+        } else if options.locked {
+            mismatches += usize::from(!report_check(file, &hash, lock.get(file)?));
+        } else if options.update_lock {
+            lock.insert(file, hash)?;
+        } else if files.len() == 1 {
+            println!("{}", hash);
+        } else {
+            println!("{}  {}", hash, file.display());
+        }
+
+        if options.tree {
+            for (hash, label) in &components {
+                println!("  {}  {}", hash, label);
+            }
+        }
+    }
+
+    if options.update_lock {
+        lock.save(options.lockfile)?;
+        println!(
+            "Recorded {} plan hash(es) in {}",
+            files.len(),
+            options.lockfile.display()
+        );
+    }
+
+    if mismatches > 0 {
+        let hint = if options.locked {
+            format!(
+                "; if the change is intended, run `kanoniv hash --update-lock <specs>` and commit {}",
+                options.lockfile.display()
+            )
+        } else {
+            String::new()
+        };
+        return Err(anyhow!(
+            "{} of {} spec(s) do not match the expected plan hash{}",
+            mismatches,
+            files.len(),
+            hint
+        ));
+    }
+
+    Ok(())
+}
+
+/// A spec's hash and, for plan hashes, the `(hash, label)` of each component.
+fn hash_file(file: &Path, strict: bool) -> Result<(String, Vec<(String, String)>)> {
     // Read file
     let content = fs::read_to_string(file)
         .with_context(|| format!("Failed to read file: {}", file.display()))?;
 
-    // Parse YAML to JSON for canonical representation
-    let spec: serde_json::Value =
-        serde_yaml::from_str(&content).with_context(|| "Failed to parse YAML")?;
+    if strict {
+        let hash = hashing::strict_hash(&content).with_context(|| "Failed to parse YAML")?;
+        return Ok((hash, Vec::new()));
+    }
 
-    // Convert to canonical JSON (sorted keys, no whitespace variation)
-    let canonical = serde_json::to_string(&spec)?;
+    // Parse YAML
+    let spec = parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?;
 
-    // Compute SHA-256 hash
-    let mut hasher = Sha256::new();
-    hasher.update(canonical.as_bytes());
-    let hash = format!("sha256:{:x}", hasher.finalize());
+    // The hash covers the compiled plan, so defaults and formatting do not leak in
+    let plan = compile::compile_to_ir(&spec)?;
 
-    println!("{}", hash);
+    let mut components = Vec::new();
+    for source in &plan.sources {
+        components.push((source.hash.clone(), format!("sources.{}", source.name)));
+    }
+    for rule in &plan.rules {
+        components.push((rule.hash.clone(), format!("rules.{}", rule.name)));
+    }
+    components.push((plan.blocking.hash.clone(), "blocking".to_string()));
+    components.push((plan.decision.hash.clone(), "decision".to_string()));
 
-    Ok(())
+    Ok((plan.plan_hash, components))
+}
+
+/// Print whether `file` hashes to `expected`; returns true on a match.
+fn report_check(file: &Path, hash: &str, expected: Option<&str>) -> bool {
+    match expected {
+        Some(expected) if expected == hash => {
+            println!("{} {} {}", "✓".green().bold(), file.display(), hash);
+            true
+        }
+        Some(expected) => {
+            eprintln!("{} {} plan hash changed", "✗".red().bold(), file.display());
+            eprintln!("    expected: {}", expected);
+            eprintln!("    actual:   {}", hash);
+            false
+        }
+        None => {
+            eprintln!(
+                "{} {} has no entry in the lockfile",
+                "✗".red().bold(),
+                file.display()
+            );
+            eprintln!("    actual:   {}", hash);
+            false
+        }
+    }
 }
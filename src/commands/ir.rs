@@ -0,0 +1,157 @@
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::commands::validate::{print_diagnostics, FileReport, Stage};
+use crate::diagnostic::{Code, Diagnostic};
+use crate::hashing;
+use crate::ir::Plan;
+use crate::schema;
+
+/// Print the JSON Schema for compiled plans.
+pub fn schema() -> Result<()> {
+    print!("{}", schema::ir_schema_source());
+    Ok(())
+}
+
+/// Check compiled plans against the IR schema and their hash tree.
+pub fn validate(paths: &[PathBuf], format: &str) -> Result<()> {
+    let reports = paths
+        .iter()
+        .map(|path| {
+            let name = path.display().to_string();
+            match fs::read_to_string(path).with_context(|| format!("Failed to read file: {}", name))
+            {
+                Ok(content) => check(&name, &content),
+                Err(e) => FileReport {
+                    path: name,
+                    valid: false,
+                    stage: Stage::Syntax,
+                    errors: vec![Diagnostic::error(
+                        Code::SchemaViolation,
+                        "",
+                        format!("{:#}", e),
+                    )],
+                    warnings: Vec::new(),
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let invalid = reports.iter().filter(|r| !r.valid).count();
+
+    if format == "json" {
+        let output = serde_json::json!({
+            "valid": invalid == 0,
+            "files": reports,
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        for report in &reports {
+            if report.valid {
+                println!("{} {} is a valid plan", "✓".green().bold(), report.path);
+            } else {
+                eprintln!("{} {}", "✗".red().bold(), report.path);
+                print_diagnostics(&report.path, &report.errors);
+            }
+        }
+    }
+
+    if invalid > 0 {
+        return Err(anyhow!(
+            "{} of {} plan(s) failed validation",
+            invalid,
+            reports.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Parse a compiled plan, check it against the IR schema and make sure
+/// every hash in its tree still matches the content.
+pub fn check(name: &str, content: &str) -> FileReport {
+    let (stage, errors) = match serde_json::from_str(content) {
+        Err(e) => (
+            Stage::Syntax,
+            vec![Diagnostic::error(
+                Code::SchemaViolation,
+                "",
+                format!("Failed to parse JSON: {}", e),
+            )],
+        ),
+        Ok(plan) => match schema::validate_ir(&plan) {
+            Ok(errors) if errors.is_empty() => {
+                let mismatches = verify_hashes(plan);
+                if mismatches.is_empty() {
+                    (Stage::Passed, mismatches)
+                } else {
+                    (Stage::Semantic, mismatches)
+                }
+            }
+            Ok(errors) => (Stage::Schema, errors),
+            Err(e) => (
+                Stage::Schema,
+                vec![Diagnostic::error(Code::SchemaViolation, "", e.to_string())],
+            ),
+        },
+    };
+
+    FileReport {
+        path: name.to_string(),
+        valid: stage == Stage::Passed,
+        stage,
+        errors,
+        warnings: Vec::new(),
+    }
+}
+
+fn verify_hashes(plan: Value) -> Vec<Diagnostic> {
+    let Ok(stored) = serde_json::from_value::<Plan>(plan) else {
+        return Vec::new();
+    };
+    let mut expected = stored.clone();
+    if hashing::hash_plan(&mut expected).is_err() {
+        return Vec::new();
+    }
+
+    let mut pairs = vec![(
+        "plan_hash".to_string(),
+        &stored.plan_hash,
+        &expected.plan_hash,
+    )];
+    for (i, (s, e)) in stored.sources.iter().zip(&expected.sources).enumerate() {
+        pairs.push((format!("sources[{}].hash", i), &s.hash, &e.hash));
+    }
+    for (i, (s, e)) in stored.rules.iter().zip(&expected.rules).enumerate() {
+        pairs.push((format!("rules[{}].hash", i), &s.hash, &e.hash));
+    }
+    pairs.push((
+        "blocking.hash".to_string(),
+        &stored.blocking.hash,
+        &expected.blocking.hash,
+    ));
+    pairs.push((
+        "decision.hash".to_string(),
+        &stored.decision.hash,
+        &expected.decision.hash,
+    ));
+
+    pairs
+        .into_iter()
+        .filter(|(_, stored, expected)| stored != expected)
+        .map(|(path, stored, expected)| {
+            Diagnostic::error(
+                Code::PlanHashMismatch,
+                path.clone(),
+                format!(
+                    "{} {} does not match the plan content ({})",
+                    path, stored, expected
+                ),
+            )
+            .with_help("The plan was edited after compilation; recompile the spec")
+        })
+        .collect()
+}
@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::commands::validate::{self, print_diagnostics};
+use crate::merge::{self, Conflict};
+
+/// Merge `ours` and `theirs` against `base`.
+///
+/// The merged spec is always written (to `output`, or printed), so that as a
+/// git merge driver the file holds the merge result; conflicted fields keep
+/// our value. Conflicts or an invalid result make the command fail.
+pub fn run(base: &Path, ours: &Path, theirs: &Path, output: Option<&Path>) -> Result<()> {
+    let read = |file: &Path| {
+        fs::read_to_string(file).with_context(|| format!("Failed to read file: {}", file.display()))
+    };
+    let result = merge::merge(&read(base)?, &read(ours)?, &read(theirs)?)?;
+
+    if !result.preserved {
+        eprintln!(
+            "{} Comments and formatting were not kept: the merge changes more than values",
+            "!".yellow().bold()
+        );
+    }
+
+    if let Some(output_path) = output {
+        fs::write(output_path, &result.content)?;
+        println!("Merged into: {}", output_path.display());
+    } else {
+        print!("{}", result.content);
+    }
+
+    if !result.is_clean() {
+        eprintln!(
+            "{} {} conflict(s), kept our side:",
+            "✗".red().bold(),
+            result.conflicts.len()
+        );
+        for conflict in &result.conflicts {
+            print_conflict(conflict);
+        }
+        return Err(anyhow!(
+            "Merge has {} conflict(s) to resolve",
+            result.conflicts.len()
+        ));
+    }
+
+    // Both sides can be valid and still combine into an invalid spec
+    let name = output.unwrap_or(ours).display().to_string();
+    let report = validate::check(&name, &result.content);
+    if !report.valid {
+        eprintln!("{} Merged spec is invalid:", "✗".red().bold());
+        print_diagnostics(&name, &report.errors);
+        return Err(anyhow!(
+            "{} validation error(s) after merging",
+            report.errors.len()
+        ));
+    }
+
+    Ok(())
+}
+
+fn print_conflict(conflict: &Conflict) {
+    let location = if conflict.field.is_empty() {
+        conflict.component.clone()
+    } else {
+        format!("{}: {}", conflict.component, conflict.field)
+    };
+    eprintln!("  {} {}", "→".red(), location.bold());
+    eprintln!(
+        "    base: {}  ours: {}  theirs: {}",
+        shown(&conflict.base),
+        shown(&conflict.ours),
+        shown(&conflict.theirs)
+    );
+}
+
+fn shown(value: &Option<Value>) -> String {
+    match value {
+        None => "(absent)".to_string(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
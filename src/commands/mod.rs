@@ -1,4 +1,10 @@
 pub mod compile;
+pub mod coverage;
 pub mod diff;
+pub mod explain;
 pub mod hash;
+pub mod ir;
+pub mod merge;
+pub mod patch;
+pub mod schema;
 pub mod validate;
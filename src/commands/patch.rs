@@ -0,0 +1,58 @@
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+
+use crate::commands::validate::{self, print_diagnostics};
+use crate::patch::{self, Patch};
+
+/// Apply a JSON Patch to a spec and write the result if it still validates.
+pub fn run(file: &Path, patch_file: &Path, output: Option<&Path>, in_place: bool) -> Result<()> {
+    let content = fs::read_to_string(file)
+        .with_context(|| format!("Failed to read file: {}", file.display()))?;
+    let patch_text = fs::read_to_string(patch_file)
+        .with_context(|| format!("Failed to read file: {}", patch_file.display()))?;
+    let patch: Patch = serde_json::from_str(&patch_text).with_context(|| {
+        format!(
+            "{} is not a JSON Patch (RFC 6902) document",
+            patch_file.display()
+        )
+    })?;
+
+    let patched = patch::apply(&content, &patch)
+        .map_err(|e| anyhow!("Failed to patch {}: {:#}", file.display(), e))?;
+
+    // Never write a spec the patch has broken
+    let name = file.display().to_string();
+    let report = validate::check(&name, &patched.content);
+    if !report.valid {
+        eprintln!("{} Patched spec is invalid:", "✗".red().bold());
+        print_diagnostics(&name, &report.errors);
+        return Err(anyhow!(
+            "{} validation error(s) after patching; {} left unchanged",
+            report.errors.len(),
+            file.display()
+        ));
+    }
+
+    if !patched.preserved {
+        eprintln!(
+            "{} Comments and formatting were not kept: the patch does more than replace values and add or remove list items",
+            "!".yellow().bold()
+        );
+    }
+
+    let target = if in_place { Some(file) } else { output };
+    if let Some(target) = target {
+        fs::write(target, &patched.content)?;
+        println!(
+            "Applied {} operation(s) to: {}",
+            patch.0.len(),
+            target.display()
+        );
+    } else {
+        print!("{}", patched.content);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::schema;
+use crate::spec::SUPPORTED_API_VERSIONS;
+
+const MODELINE_PREFIX: &str = "# yaml-language-server: $schema=";
+
+pub fn run(
+    api_version: &str,
+    output: Option<&Path>,
+    modeline: &[PathBuf],
+    vscode: Option<&str>,
+) -> Result<()> {
+    let source = schema::schema_source(api_version).ok_or_else(|| {
+        anyhow!(
+            "Unknown api_version '{}'. Supported: {}",
+            api_version,
+            SUPPORTED_API_VERSIONS.join(", ")
+        )
+    })?;
+
+    // Editors load the schema we write locally; clap requires `-o` for
+    // `--modeline` and `--vscode`
+    let Some(output_path) = output else {
+        print!("{}", source);
+        return Ok(());
+    };
+    fs::write(output_path, source)
+        .with_context(|| format!("Failed to write file: {}", output_path.display()))?;
+    println!("Wrote schema to: {}", output_path.display());
+
+    for file in modeline {
+        let base = file.parent().unwrap_or(Path::new(""));
+        write_modeline(file, &schema_ref(output_path, base))?;
+        println!("Added schema modeline to: {}", file.display());
+    }
+
+    if let Some(glob) = vscode {
+        let settings = Path::new(".vscode/settings.json");
+        write_vscode_mapping(settings, &schema_ref(output_path, Path::new("")), glob)?;
+        println!("Mapped '{}' to the schema in: {}", glob, settings.display());
+    }
+
+    Ok(())
+}
+
+/// Reference to the schema at `path` as seen from `base` (a directory
+/// relative to the working directory).
+fn schema_ref(path: &Path, base: &Path) -> String {
+    let relative = relative_path(path, base);
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    if relative.starts_with("../") || relative.starts_with('/') {
+        relative
+    } else {
+        format!("./{}", relative)
+    }
+}
+
+/// Add or replace the `yaml-language-server` modeline at the top of a spec.
+fn write_modeline(file: &Path, schema_ref: &str) -> Result<()> {
+    let content = fs::read_to_string(file)
+        .with_context(|| format!("Failed to read file: {}", file.display()))?;
+
+    let body: Vec<&str> = content
+        .lines()
+        .filter(|line| !line.starts_with(MODELINE_PREFIX))
+        .collect();
+    let mut updated = format!("{}{}\n", MODELINE_PREFIX, schema_ref);
+    updated.push_str(&body.join("\n"));
+    if content.ends_with('\n') {
+        updated.push('\n');
+    }
+
+    fs::write(file, updated).with_context(|| format!("Failed to write file: {}", file.display()))
+}
+
+/// Register `glob` under `yaml.schemas` in a VS Code settings file.
+fn write_vscode_mapping(settings_path: &Path, schema_ref: &str, glob: &str) -> Result<()> {
+    let mut settings: Value = if settings_path.exists() {
+        let content = fs::read_to_string(settings_path)
+            .with_context(|| format!("Failed to read file: {}", settings_path.display()))?;
+        serde_json::from_str(&content).with_context(|| {
+            format!(
+                "Failed to parse {} (comments are not supported; add the mapping by hand)",
+                settings_path.display()
+            )
+        })?
+    } else {
+        serde_json::json!({})
+    };
+
+    let settings_obj = settings
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("{} is not a JSON object", settings_path.display()))?;
+    let schemas = settings_obj
+        .entry("yaml.schemas")
+        .or_insert_with(|| serde_json::json!({}))
+        .as_object_mut()
+        .ok_or_else(|| {
+            anyhow!(
+                "'yaml.schemas' in {} is not an object",
+                settings_path.display()
+            )
+        })?;
+
+    // A glob maps to a single schema, so drop it from any previous mapping
+    for (_, globs) in schemas.iter_mut() {
+        if let Some(list) = globs.as_array_mut() {
+            list.retain(|g| g.as_str() != Some(glob));
+        }
+    }
+    schemas.retain(|_, globs| globs.as_array().is_none_or(|list| !list.is_empty()));
+    schemas
+        .entry(schema_ref)
+        .or_insert_with(|| serde_json::json!([]))
+        .as_array_mut()
+        .ok_or_else(|| anyhow!("Unexpected mapping for {} in 'yaml.schemas'", schema_ref))?
+        .push(Value::String(glob.to_string()));
+
+    if let Some(parent) = settings_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(
+        settings_path,
+        serde_json::to_string_pretty(&settings)? + "\n",
+    )
+    .with_context(|| format!("Failed to write file: {}", settings_path.display()))
+}
+
+/// Path to `target` relative to the directory `base`, both relative to the
+/// working directory (or both absolute).
+fn relative_path(target: &Path, base: &Path) -> PathBuf {
+    let target: Vec<Component> = target
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+    let base: Vec<Component> = base
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+
+    let common = target.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for component in &target[common..] {
+        relative.push(component.as_os_str());
+    }
+    relative
+}
@@ -1,60 +1,449 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use colored::Colorize;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Serialize;
 use std::fs;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
-use crate::parser;
+use crate::diagnostic::{Code, Diagnostic, Severity};
+use crate::git;
+use crate::lint::{self, LintOptions};
+use crate::parser::{self, SourceMap, Span};
+use crate::schema;
+use crate::spec::Spec;
 use crate::validator;
+use crate::versioning::{self, VersionScheme};
 
-pub fn run(file: &Path, format: &str) -> Result<()> {
-    // Read file
-    let content = fs::read_to_string(file)
-        .with_context(|| format!("Failed to read file: {}", file.display()))?;
+/// Patterns matched when walking a directory and no `--include` is given.
+const DEFAULT_INCLUDE: &[&str] = &["*.yaml", "*.yml"];
 
-    // Parse YAML
-    let spec = parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?;
+/// Display name used for a spec read from standard input.
+const STDIN_NAME: &str = "<stdin>";
 
-    // Validate schema
-    let schema_errors = validator::validate_schema(&spec)?;
-    if !schema_errors.is_empty() {
-        if format == "json" {
-            println!("{}", serde_json::to_string_pretty(&schema_errors)?);
-        } else {
-            eprintln!("{} Schema validation failed:", "✗".red().bold());
-            for error in &schema_errors {
-                eprintln!("  {} {}", "→".red(), error);
+/// The stage a spec failed at, or `Passed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Syntax,
+    Schema,
+    Semantic,
+    /// Valid, but lint warnings were denied
+    Lint,
+    Passed,
+}
+
+/// Validation outcome for one spec.
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub path: String,
+    pub valid: bool,
+    pub stage: Stage,
+    pub errors: Vec<Diagnostic>,
+    /// Lint findings (warnings and info); only valid specs are linted
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Validate specs. With `version_bump`, each valid spec must also bump its
+/// `identity_version` if its plan changed since that git revision.
+pub fn run(
+    paths: &[PathBuf],
+    format: &str,
+    include: &[String],
+    exclude: &[String],
+    version_bump: Option<(&str, VersionScheme)>,
+    lints: &LintOptions,
+) -> Result<()> {
+    let inputs = collect_inputs(paths, include, exclude)?;
+    if inputs.is_empty() {
+        return Err(anyhow!("No spec files found"));
+    }
+
+    // A single file keeps the detailed, stage-by-stage output
+    if let [input] = inputs.as_slice() {
+        return run_single(input, format, version_bump, lints);
+    }
+
+    let reports = inputs
+        .iter()
+        .map(|input| {
+            let name = display_name(input);
+            match read_input(input) {
+                Ok(content) => {
+                    let report = check_with(&name, &content, lints);
+                    check_version_bump(report, input, &content, version_bump)
+                }
+                Err(e) => FileReport {
+                    path: name,
+                    valid: false,
+                    stage: Stage::Syntax,
+                    errors: vec![Diagnostic::error(Code::InvalidYaml, "", format!("{:#}", e))],
+                    warnings: Vec::new(),
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let invalid = reports.iter().filter(|r| !r.valid).count();
+    let error_count: usize = reports.iter().map(|r| r.errors.len()).sum();
+    let warning_count = reports
+        .iter()
+        .flat_map(|r| &r.warnings)
+        .filter(|w| w.severity == Severity::Warning)
+        .count();
+
+    if format == "json" {
+        let output = serde_json::json!({
+            "valid": invalid == 0,
+            "files": reports,
+            "summary": {
+                "files": reports.len(),
+                "valid": reports.len() - invalid,
+                "invalid": invalid,
+                "errors": error_count,
+                "warnings": warning_count,
+            },
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        for report in &reports {
+            if report.valid {
+                println!("{} {}", "✓".green().bold(), report.path);
+            } else {
+                eprintln!("{} {}", "✗".red().bold(), report.path);
+                print_diagnostics(&report.path, &report.errors);
             }
+            print_findings(&report.path, &report.warnings);
         }
-        return Err(anyhow::anyhow!("{} schema error(s)", schema_errors.len()));
+        println!();
+        println!(
+            "{} file(s) checked: {} valid, {} invalid ({} error(s), {} warning(s))",
+            reports.len(),
+            reports.len() - invalid,
+            invalid,
+            error_count,
+            warning_count
+        );
     }
 
-    if format == "text" {
-        println!("{} Schema valid", "✓".green().bold());
+    if invalid > 0 {
+        return Err(anyhow!(
+            "{} of {} file(s) failed validation",
+            invalid,
+            reports.len()
+        ));
     }
 
-    // Validate semantics
-    let semantic_errors = validator::validate_semantics(&spec)?;
-    if !semantic_errors.is_empty() {
-        if format == "json" {
-            println!("{}", serde_json::to_string_pretty(&semantic_errors)?);
-        } else {
-            eprintln!("{} Semantic validation failed:", "✗".red().bold());
-            for error in &semantic_errors {
-                eprintln!("  {} {}", "→".red(), error);
+    Ok(())
+}
+
+fn run_single(
+    input: &Path,
+    format: &str,
+    version_bump: Option<(&str, VersionScheme)>,
+    lints: &LintOptions,
+) -> Result<()> {
+    let name = display_name(input);
+    let content = read_input(input)?;
+    let report = check_with(&name, &content, lints);
+    let report = check_version_bump(report, input, &content, version_bump);
+
+    match report.stage {
+        Stage::Syntax => {
+            report_failure(&name, format, "YAML parsing", &report.errors)?;
+            Err(anyhow!("Failed to parse YAML"))
+        }
+        Stage::Schema => {
+            report_failure(&name, format, "Schema validation", &report.errors)?;
+            Err(anyhow!("{} schema error(s)", report.errors.len()))
+        }
+        Stage::Semantic => {
+            if format == "text" {
+                println!("{} Schema valid", "✓".green().bold());
             }
+            report_failure(&name, format, "Semantic validation", &report.errors)?;
+            Err(anyhow!("{} semantic error(s)", report.errors.len()))
         }
-        return Err(anyhow::anyhow!(
-            "{} semantic error(s)",
-            semantic_errors.len()
-        ));
+        Stage::Lint => {
+            if format == "text" {
+                println!("{} Schema valid", "✓".green().bold());
+                println!("{} Semantic checks passed", "✓".green().bold());
+            }
+            let denied: Vec<Diagnostic> = report
+                .warnings
+                .into_iter()
+                .filter(|w| w.severity == Severity::Warning)
+                .collect();
+            report_failure(&name, format, "Lint", &denied)?;
+            Err(anyhow!(
+                "{} warning(s) denied by --deny-warnings",
+                denied.len()
+            ))
+        }
+        Stage::Passed => {
+            if format == "text" {
+                println!("{} Schema valid", "✓".green().bold());
+                println!("{} Semantic checks passed", "✓".green().bold());
+                print_findings(&name, &report.warnings);
+                println!("{} {} is valid", "✓".green().bold(), name);
+            } else if report.warnings.is_empty() {
+                println!(r#"{{"valid": true, "errors": []}}"#);
+            } else {
+                let output = serde_json::json!({
+                    "valid": true,
+                    "errors": [],
+                    "warnings": report.warnings,
+                });
+                println!("{}", serde_json::to_string_pretty(&output)?);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Run the syntax, schema and semantic stages, stopping at the first that
+/// fails, then lint the spec with the default options.
+pub fn check(name: &str, content: &str) -> FileReport {
+    check_with(name, content, &LintOptions::default())
+}
+
+/// [`check`] with the given lint options.
+pub fn check_with(name: &str, content: &str, lints: &LintOptions) -> FileReport {
+    let failed = |stage, errors| FileReport {
+        path: name.to_string(),
+        valid: false,
+        stage,
+        errors,
+        warnings: Vec::new(),
+    };
+
+    let document = match parser::parse_value(content) {
+        Ok(document) => document,
+        Err(e) => return failed(Stage::Syntax, vec![syntax_error(&e)]),
+    };
+    let source_map = SourceMap::from_yaml(content).unwrap_or_default();
+
+    let schema_errors = match schema::validate_document(&document) {
+        Ok(errors) => errors,
+        Err(e) => vec![Diagnostic::error(Code::SchemaViolation, "", e.to_string())],
+    };
+    if !schema_errors.is_empty() {
+        return failed(Stage::Schema, locate_all(schema_errors, &source_map));
+    }
+
+    let spec = match serde_json::from_value::<Spec>(document) {
+        Ok(spec) => spec,
+        Err(e) => {
+            let error = Diagnostic::error(Code::SchemaViolation, "", e.to_string());
+            return failed(Stage::Semantic, vec![error]);
+        }
+    };
+    let semantic_errors = validator::validate_semantics(&spec)
+        .unwrap_or_else(|e| vec![Diagnostic::error(Code::SchemaViolation, "", e.to_string())]);
+    if !semantic_errors.is_empty() {
+        return failed(Stage::Semantic, locate_all(semantic_errors, &source_map));
+    }
+
+    let warnings = locate_all(lint::run(&spec, lints), &source_map);
+    let denied = lints.denies(&warnings);
+    FileReport {
+        path: name.to_string(),
+        valid: !denied,
+        stage: if denied { Stage::Lint } else { Stage::Passed },
+        errors: Vec::new(),
+        warnings,
+    }
+}
+
+/// Fail a valid spec whose plan changed since `rev` without the
+/// `identity_version` bump `scheme` asks for. Specs that are new since `rev`,
+/// or read from stdin, are left alone.
+fn check_version_bump(
+    report: FileReport,
+    input: &Path,
+    content: &str,
+    version_bump: Option<(&str, VersionScheme)>,
+) -> FileReport {
+    let Some((rev, scheme)) = version_bump else {
+        return report;
+    };
+    if !report.valid || input.as_os_str() == "-" {
+        return report;
     }
 
-    if format == "text" {
-        println!("{} Semantic checks passed", "✓".green().bold());
-        println!("{} {} is valid", "✓".green().bold(), file.display());
+    let errors = git::show(rev, input)
+        .and_then(|old| match old {
+            Some(old) => versioning::check_bump(&old, content, scheme),
+            None => Ok(Vec::new()),
+        })
+        .unwrap_or_else(|e| {
+            vec![Diagnostic::error(
+                Code::IdentityVersionNotBumped,
+                "identity_version",
+                format!("Cannot compare with {}: {:#}", rev, e),
+            )]
+        });
+    if errors.is_empty() {
+        return report;
+    }
+
+    let source_map = SourceMap::from_yaml(content).unwrap_or_default();
+    FileReport {
+        valid: false,
+        stage: Stage::Semantic,
+        errors: locate_all(errors, &source_map),
+        ..report
+    }
+}
+
+fn syntax_error(error: &anyhow::Error) -> Diagnostic {
+    let mut diagnostic = Diagnostic::error(Code::InvalidYaml, "", error.to_string());
+    diagnostic.span = error
+        .downcast_ref::<serde_yaml::Error>()
+        .and_then(|e| e.location())
+        .map(|location| Span {
+            line: location.line(),
+            column: location.column(),
+        });
+    diagnostic
+}
+
+fn locate_all(errors: Vec<Diagnostic>, source_map: &SourceMap) -> Vec<Diagnostic> {
+    errors.into_iter().map(|e| e.locate(source_map)).collect()
+}
+
+fn report_failure(name: &str, format: &str, stage: &str, errors: &[Diagnostic]) -> Result<()> {
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(errors)?);
+        return Ok(());
+    }
+
+    eprintln!("{} {} failed:", "✗".red().bold(), stage);
+    print_diagnostics(name, errors);
+    Ok(())
+}
+
+/// Print lint findings, which unlike errors do not fail the spec.
+fn print_findings(name: &str, findings: &[Diagnostic]) {
+    for finding in findings {
+        let marker = match finding.severity {
+            Severity::Info => "ℹ".blue(),
+            _ => "⚠".yellow(),
+        };
+        println!("  {} {}", marker, finding);
+        if let Some(help) = &finding.help {
+            println!("    {} {}", "help:".cyan(), help);
+        }
+        if let Some(span) = finding.span {
+            println!(
+                "    {}",
+                format!("at {}:{}:{}", name, span.line, span.column).dimmed()
+            );
+        }
+    }
+}
+
+pub(crate) fn print_diagnostics(name: &str, errors: &[Diagnostic]) {
+    for error in errors {
+        eprintln!("  {} {}", "→".red(), error);
+        if let Some(help) = &error.help {
+            eprintln!("    {} {}", "help:".cyan(), help);
+        }
+        if let Some(span) = error.span {
+            eprintln!(
+                "    {}",
+                format!("at {}:{}:{}", name, span.line, span.column).dimmed()
+            );
+        }
+    }
+}
+
+/// Expand the command line paths into the list of specs to check.
+///
+/// Files are taken as given, directories are walked recursively and filtered
+/// with `include` (default `*.yaml`, `*.yml`), and `-` stands for stdin and
+/// may be given once. `exclude` applies to everything except stdin.
+fn collect_inputs(
+    paths: &[PathBuf],
+    include: &[String],
+    exclude: &[String],
+) -> Result<Vec<PathBuf>> {
+    let include = if include.is_empty() {
+        build_globset(DEFAULT_INCLUDE.iter().copied())?
     } else {
-        println!(r#"{{"valid": true, "errors": []}}"#);
+        build_globset(include.iter().map(String::as_str))?
+    };
+    let exclude = build_globset(exclude.iter().map(String::as_str))?;
+
+    let mut inputs = Vec::new();
+    for path in paths {
+        if path.as_os_str() == "-" {
+            if inputs.contains(path) {
+                bail!("'-' (stdin) can only be given once");
+            }
+            inputs.push(path.clone());
+        } else if path.is_dir() {
+            walk_dir(path, path, &include, &exclude, &mut inputs)?;
+        } else if !exclude.is_match(path) {
+            inputs.push(path.clone());
+        }
     }
+    Ok(inputs)
+}
+
+fn walk_dir(
+    root: &Path,
+    dir: &Path,
+    include: &GlobSet,
+    exclude: &GlobSet,
+    found: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
 
+    for entry in entries {
+        let path = entry.path();
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        if exclude.is_match(relative) || exclude.is_match(&path) {
+            continue;
+        }
+        // Symlinked directories are not followed, so a link cycle cannot
+        // repeat specs
+        if entry.file_type()?.is_dir() {
+            walk_dir(root, &path, include, exclude, found)?;
+        } else if include.is_match(relative) {
+            found.push(path);
+        }
+    }
     Ok(())
 }
+
+fn build_globset<'a>(patterns: impl Iterator<Item = &'a str>) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).with_context(|| format!("Invalid glob: {}", pattern))?);
+    }
+    Ok(builder.build()?)
+}
+
+fn display_name(input: &Path) -> String {
+    if input.as_os_str() == "-" {
+        STDIN_NAME.to_string()
+    } else {
+        input.display().to_string()
+    }
+}
+
+fn read_input(input: &Path) -> Result<String> {
+    if input.as_os_str() == "-" {
+        let mut content = String::new();
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .context("Failed to read from stdin")?;
+        return Ok(content);
+    }
+    fs::read_to_string(input).with_context(|| format!("Failed to read file: {}", input.display()))
+}
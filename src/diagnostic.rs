@@ -0,0 +1,284 @@
+//! Structured validation diagnostics.
+//!
+//! Every finding carries a stable code (`K0001`) and a kebab-case name
+//! (`missing-required-field`) so tools can filter and group without parsing
+//! the message text. Codes are never reused or renumbered.
+
+use serde::Serialize;
+use std::fmt;
+
+use crate::parser::{SourceMap, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// Stable diagnostic codes. `K0000` is a YAML syntax error, other `K00xx`
+/// are structural, `K01xx` semantic and `K02xx` lints (see [`crate::lint`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Code {
+    InvalidYaml,
+    MissingRequiredField,
+    InvalidApiVersion,
+    TooManyRules,
+    WeightOutOfRange,
+    ThresholdOutOfRange,
+    TooManySources,
+    TooManyBlockingKeys,
+    UnsupportedApiVersion,
+    InvalidType,
+    InvalidEnumValue,
+    UnknownProperty,
+    ValueOutOfRange,
+    SchemaViolation,
+    UnsupportedIrVersion,
+    PlanHashMismatch,
+    UnknownFieldReference,
+    DuplicateRuleName,
+    DuplicateSourceName,
+    ThresholdOrder,
+    IdentityVersionNotBumped,
+    UnknownRuleType,
+    InvalidRuleParameter,
+    IncompatibleRuleField,
+    UnresolvedBlockingKey,
+    InvalidBlockingParameter,
+    ZeroWeight,
+    IncompleteThresholds,
+    UnusedAttribute,
+    UnknownBlockingKey,
+    IneffectiveThreshold,
+    UnreachableThreshold,
+    IgnoredParameter,
+    ImplicitParameter,
+}
+
+impl Code {
+    pub const ALL: &'static [Code] = &[
+        Code::InvalidYaml,
+        Code::MissingRequiredField,
+        Code::InvalidApiVersion,
+        Code::TooManyRules,
+        Code::WeightOutOfRange,
+        Code::ThresholdOutOfRange,
+        Code::TooManySources,
+        Code::TooManyBlockingKeys,
+        Code::UnsupportedApiVersion,
+        Code::InvalidType,
+        Code::InvalidEnumValue,
+        Code::UnknownProperty,
+        Code::ValueOutOfRange,
+        Code::SchemaViolation,
+        Code::UnsupportedIrVersion,
+        Code::PlanHashMismatch,
+        Code::UnknownFieldReference,
+        Code::DuplicateRuleName,
+        Code::DuplicateSourceName,
+        Code::ThresholdOrder,
+        Code::IdentityVersionNotBumped,
+        Code::UnknownRuleType,
+        Code::InvalidRuleParameter,
+        Code::IncompatibleRuleField,
+        Code::UnresolvedBlockingKey,
+        Code::InvalidBlockingParameter,
+        Code::ZeroWeight,
+        Code::IncompleteThresholds,
+        Code::UnusedAttribute,
+        Code::UnknownBlockingKey,
+        Code::IneffectiveThreshold,
+        Code::UnreachableThreshold,
+        Code::IgnoredParameter,
+        Code::ImplicitParameter,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Code::InvalidYaml => "K0000",
+            Code::MissingRequiredField => "K0001",
+            Code::InvalidApiVersion => "K0002",
+            Code::TooManyRules => "K0003",
+            Code::WeightOutOfRange => "K0004",
+            Code::ThresholdOutOfRange => "K0005",
+            Code::TooManySources => "K0006",
+            Code::TooManyBlockingKeys => "K0007",
+            Code::UnsupportedApiVersion => "K0008",
+            Code::InvalidType => "K0009",
+            Code::InvalidEnumValue => "K0010",
+            Code::UnknownProperty => "K0011",
+            Code::ValueOutOfRange => "K0012",
+            Code::SchemaViolation => "K0013",
+            Code::UnsupportedIrVersion => "K0014",
+            Code::PlanHashMismatch => "K0015",
+            Code::UnknownFieldReference => "K0101",
+            Code::DuplicateRuleName => "K0102",
+            Code::DuplicateSourceName => "K0103",
+            Code::ThresholdOrder => "K0104",
+            Code::IdentityVersionNotBumped => "K0105",
+            Code::UnknownRuleType => "K0106",
+            Code::InvalidRuleParameter => "K0107",
+            Code::IncompatibleRuleField => "K0108",
+            Code::UnresolvedBlockingKey => "K0109",
+            Code::InvalidBlockingParameter => "K0110",
+            Code::ZeroWeight => "K0201",
+            Code::IncompleteThresholds => "K0202",
+            Code::UnusedAttribute => "K0203",
+            Code::UnknownBlockingKey => "K0204",
+            Code::IneffectiveThreshold => "K0205",
+            Code::UnreachableThreshold => "K0206",
+            Code::IgnoredParameter => "K0207",
+            Code::ImplicitParameter => "K0208",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Code::InvalidYaml => "invalid-yaml",
+            Code::MissingRequiredField => "missing-required-field",
+            Code::InvalidApiVersion => "invalid-api-version",
+            Code::TooManyRules => "too-many-rules",
+            Code::WeightOutOfRange => "weight-out-of-range",
+            Code::ThresholdOutOfRange => "threshold-out-of-range",
+            Code::TooManySources => "too-many-sources",
+            Code::TooManyBlockingKeys => "too-many-blocking-keys",
+            Code::UnsupportedApiVersion => "unsupported-api-version",
+            Code::InvalidType => "invalid-type",
+            Code::InvalidEnumValue => "invalid-enum-value",
+            Code::UnknownProperty => "unknown-property",
+            Code::ValueOutOfRange => "value-out-of-range",
+            Code::SchemaViolation => "schema-violation",
+            Code::UnsupportedIrVersion => "unsupported-ir-version",
+            Code::PlanHashMismatch => "plan-hash-mismatch",
+            Code::UnknownFieldReference => "unknown-field-reference",
+            Code::DuplicateRuleName => "duplicate-rule-name",
+            Code::DuplicateSourceName => "duplicate-source-name",
+            Code::ThresholdOrder => "threshold-order",
+            Code::IdentityVersionNotBumped => "identity-version-not-bumped",
+            Code::UnknownRuleType => "unknown-rule-type",
+            Code::InvalidRuleParameter => "invalid-rule-parameter",
+            Code::IncompatibleRuleField => "incompatible-rule-field",
+            Code::UnresolvedBlockingKey => "unresolved-blocking-key",
+            Code::InvalidBlockingParameter => "invalid-blocking-parameter",
+            Code::ZeroWeight => "zero-weight",
+            Code::IncompleteThresholds => "incomplete-thresholds",
+            Code::UnusedAttribute => "unused-attribute",
+            Code::UnknownBlockingKey => "unknown-blocking-key",
+            Code::IneffectiveThreshold => "ineffective-threshold",
+            Code::UnreachableThreshold => "unreachable-threshold",
+            Code::IgnoredParameter => "ignored-parameter",
+            Code::ImplicitParameter => "implicit-parameter",
+        }
+    }
+
+    /// Look a code up by id (`K0001`) or name (`missing-required-field`).
+    pub fn parse(s: &str) -> Option<Code> {
+        Code::ALL
+            .iter()
+            .copied()
+            .find(|c| c.as_str() == s || c.name() == s)
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Code {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub code: Code,
+    /// Kebab-case name of `code`
+    pub name: &'static str,
+    pub severity: Severity,
+    /// JSON path of the offending node (`rules[3].field`)
+    pub path: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    pub fn new(
+        code: Code,
+        severity: Severity,
+        path: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Diagnostic {
+            code,
+            name: code.name(),
+            severity,
+            path: path.into(),
+            message: message.into(),
+            help: None,
+            span: None,
+        }
+    }
+
+    pub fn error(code: Code, path: impl Into<String>, message: impl Into<String>) -> Self {
+        Diagnostic::new(code, Severity::Error, path, message)
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Resolve `path` to a source position.
+    pub fn locate(mut self, source_map: &SourceMap) -> Self {
+        self.span = source_map.locate(&self.path);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
+
+/// The candidate closest to a misspelled `name`, if one is close enough to
+/// suggest.
+pub fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    candidates
+        .into_iter()
+        .map(|candidate| {
+            (
+                candidate,
+                strsim::jaro_winkler(&name, &candidate.to_lowercase()),
+            )
+        })
+        .filter(|(_, similarity)| *similarity >= 0.8)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(candidate, _)| candidate)
+}
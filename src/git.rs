@@ -0,0 +1,212 @@
+//! Reading spec versions out of the local git repository.
+//!
+//! Shells out to the `git` binary so that every revision syntax git accepts
+//! (`HEAD~1`, `origin/main`, tags, SHAs) works unchanged.
+
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+fn git(args: &[&str]) -> Result<std::process::Output> {
+    Command::new("git")
+        .args(args)
+        .output()
+        .context("Failed to run git; is it installed and on PATH?")
+}
+
+/// Content of `path` (absolute, or relative to the working directory) at
+/// `rev`, or `None` if the file does not exist in that revision.
+pub fn show(rev: &str, path: &Path) -> Result<Option<String>> {
+    let repo_path = repo_path(path)?;
+    let spec = format!("{}:{}", rev, repo_path);
+    let output = git(&["show", &spec])?;
+    if output.status.success() {
+        return Ok(Some(
+            String::from_utf8(output.stdout)
+                .with_context(|| format!("{} is not valid UTF-8", spec))?,
+        ));
+    }
+
+    // Tell a missing file apart from a bad revision
+    let verify = git(&[
+        "rev-parse",
+        "--verify",
+        "--quiet",
+        &format!("{}^{{commit}}", rev),
+    ])?;
+    if !verify.status.success() {
+        return Err(anyhow!("Unknown git revision: {}", rev));
+    }
+
+    // Only a file the revision's tree does not list is missing
+    let listing = git(&["ls-tree", "--full-tree", rev, "--", &repo_path])?;
+    if listing.status.success() && listing.stdout.is_empty() {
+        return Ok(None);
+    }
+    Err(anyhow!(
+        "git show {} failed: {}",
+        spec,
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))
+}
+
+/// Files under `paths` that differ between `base` and `head` (the working
+/// tree if `None`), relative to the working directory. Files outside the
+/// working directory come back as `../` paths rather than being left out.
+pub fn changed_files(base: &str, head: Option<&str>, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut args = vec!["diff", "--name-only", "--no-renames", base];
+    args.extend(head);
+    args.push("--");
+    let paths: Vec<String> = paths.iter().map(|p| to_git_path(p)).collect();
+    args.extend(paths.iter().map(String::as_str));
+
+    let output = git(&args)?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git diff failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let prefix = prefix()?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|name| from_repo_path(name, &prefix))
+        .collect())
+}
+
+/// Split `base..head`, `base...head` (from the merge base), `base..` or
+/// `base` into a base revision and an optional head (`None`: working tree).
+pub fn parse_range(range: &str) -> Result<(String, Option<String>)> {
+    let (base, head) = if let Some((base, head)) = range.split_once("...") {
+        let head = if head.is_empty() { "HEAD" } else { head };
+        let output = git(&["merge-base", base, head])?;
+        if !output.status.success() {
+            return Err(anyhow!("No merge base between {} and {}", base, head));
+        }
+        let merge_base = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (merge_base, Some(head.to_string()))
+    } else if let Some((base, head)) = range.split_once("..") {
+        (
+            base.to_string(),
+            Some(head.to_string()).filter(|h| !h.is_empty()),
+        )
+    } else {
+        (range.to_string(), None)
+    };
+
+    if base.is_empty() {
+        return Err(anyhow!(
+            "Invalid git range '{}': missing base revision",
+            range
+        ));
+    }
+    Ok((base, head))
+}
+
+/// Root of the work tree.
+fn toplevel() -> Result<PathBuf> {
+    Ok(PathBuf::from(rev_parse("--show-toplevel")?))
+}
+
+/// The working directory relative to the root of the work tree, `/`-ended
+/// (`specs/`), or empty at the root.
+fn prefix() -> Result<String> {
+    rev_parse("--show-prefix")
+}
+
+fn rev_parse(flag: &str) -> Result<String> {
+    let output = git(&["rev-parse", flag])?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Not in a git repository: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// `path` relative to the root of the work tree, `/`-separated.
+///
+/// Relative paths are resolved lexically from the working directory, so the
+/// file and its directories need not exist any more.
+fn repo_path(path: &Path) -> Result<String> {
+    let toplevel = toplevel()?;
+    let outside = || {
+        anyhow!(
+            "{} is outside the git work tree at {}",
+            path.display(),
+            toplevel.display()
+        )
+    };
+
+    let relative = if path.is_absolute() {
+        let path = normalize(path).ok_or_else(outside)?;
+        match path.strip_prefix(&toplevel) {
+            Ok(relative) => relative.to_path_buf(),
+            // The path may reach the work tree through a symlink
+            Err(_) => resolve_existing(&path)
+                .strip_prefix(fs::canonicalize(&toplevel)?)
+                .map_err(|_| outside())?
+                .to_path_buf(),
+        }
+    } else {
+        Path::new(&prefix()?).join(path)
+    };
+    let relative = normalize(&relative).ok_or_else(outside)?;
+    Ok(to_git_path(&relative))
+}
+
+/// `path` with `.` and `..` components resolved, or `None` if a relative
+/// path climbs above its start.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normal.pop() {
+                    return None;
+                }
+            }
+            other => normal.push(other),
+        }
+    }
+    Some(normal)
+}
+
+/// `path` with symlinks resolved in the longest of its directories that
+/// exists.
+fn resolve_existing(path: &Path) -> PathBuf {
+    path.ancestors()
+        .skip(1)
+        .find_map(|ancestor| {
+            let resolved = fs::canonicalize(ancestor).ok()?;
+            let rest = path.strip_prefix(ancestor).ok()?;
+            Some(resolved.join(rest))
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// A work tree path from `git diff` made relative to the working directory,
+/// whose own work tree path is `prefix`.
+fn from_repo_path(name: &str, prefix: &str) -> PathBuf {
+    let name: Vec<&str> = name.split('/').collect();
+    let prefix: Vec<&str> = prefix.split('/').filter(|p| !p.is_empty()).collect();
+    let common = name.iter().zip(&prefix).take_while(|(a, b)| a == b).count();
+
+    let mut path = PathBuf::new();
+    for _ in common..prefix.len() {
+        path.push("..");
+    }
+    for part in &name[common..] {
+        path.push(part);
+    }
+    path
+}
+
+fn to_git_path(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    path.strip_prefix("./").unwrap_or(&path).to_string()
+}
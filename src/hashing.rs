@@ -0,0 +1,205 @@
+//! Canonical JSON and the plan hash.
+//!
+//! Hashes are computed over a canonical JSON text in the style of RFC 8785
+//! (JSON Canonicalization Scheme):
+//!
+//! - no insignificant whitespace;
+//! - object keys sorted by their UTF-16 code units;
+//! - numbers written the way ECMAScript prints them, so `1`, `1.0` and
+//!   `1e0` are all `1`, and `0.85` stays `0.85`;
+//! - strings escaped minimally (`\"`, `\\`, the short control escapes and
+//!   `\u00xx` for other control characters).
+//!
+//! Plan hashes form a Merkle tree. Each source, rule, the blocking config
+//! and the decision section is hashed on its own (its `hash` field left
+//! out), and the plan hash covers the plan header plus those component
+//! hashes. A runtime can therefore tell exactly which components changed.
+//! Every hash is `sha256:` followed by the hex SHA-256 of a canonical text.
+//!
+//! The plan hash is semantic: because it covers the compiled plan, defaults
+//! are filled in and formatting is gone, and collections whose order has no
+//! meaning (`sources`, `rules`, each rule's `inputs`, `blocking.keys`) are
+//! sorted first: every aggregation scores the rules the same in any order.
+//! [`strict_hash`] is the byte-exact alternative.
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::ir::Plan;
+
+/// Canonical JSON text of a value.
+pub fn canonicalize(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(value, &mut out);
+    out
+}
+
+/// `sha256:<hex>` digest of some bytes.
+pub fn sha256(bytes: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(bytes))
+}
+
+/// Hash of one plan component (a source, rule, blocking or decision).
+/// Its own `hash` field is not part of the input.
+pub fn component_hash<T: Serialize>(component: &T) -> Result<String> {
+    let mut value = serde_json::to_value(component)?;
+    if let Some(object) = value.as_object_mut() {
+        object.remove("hash");
+    }
+    sort_as_set(value.get_mut("inputs"));
+    sort_as_set(value.get_mut("keys"));
+    Ok(sha256(canonicalize(&value).as_bytes()))
+}
+
+/// Fill in every component hash of a plan and then its `plan_hash`.
+pub fn hash_plan(plan: &mut Plan) -> Result<()> {
+    for source in &mut plan.sources {
+        source.hash = component_hash(source)?;
+    }
+    for rule in &mut plan.rules {
+        rule.hash = component_hash(rule)?;
+    }
+    plan.blocking.hash = component_hash(&plan.blocking)?;
+    plan.decision.hash = component_hash(&plan.decision)?;
+    plan.plan_hash = plan_hash(plan)?;
+    Ok(())
+}
+
+/// Root of the plan's hash tree, computed from the components themselves
+/// rather than any `hash` fields already stored in them.
+pub fn plan_hash(plan: &Plan) -> Result<String> {
+    let mut sources = plan
+        .sources
+        .iter()
+        .map(component_hash)
+        .collect::<Result<Vec<_>>>()?;
+    sources.sort();
+    let mut rules = plan
+        .rules
+        .iter()
+        .map(component_hash)
+        .collect::<Result<Vec<_>>>()?;
+    rules.sort();
+
+    let root = json!({
+        "ir_version": plan.ir_version,
+        "api_version": plan.api_version,
+        "identity_version": plan.identity_version,
+        "entity": plan.entity,
+        "sources": sources,
+        "rules": rules,
+        "blocking": component_hash(&plan.blocking)?,
+        "decision": component_hash(&plan.decision)?,
+    });
+    Ok(sha256(canonicalize(&root).as_bytes()))
+}
+
+/// Hash of the spec document exactly as written: the YAML is parsed and
+/// re-serialized without any normalization, so `1` and `1.0` or a reordered
+/// list give different hashes.
+pub fn strict_hash(yaml: &str) -> Result<String> {
+    let document: Value = serde_yaml::from_str(yaml)?;
+    Ok(sha256(serde_json::to_string(&document)?.as_bytes()))
+}
+
+/// Order an array by the canonical form of its items.
+fn sort_as_set(value: Option<&mut Value>) {
+    if let Some(items) = value.and_then(Value::as_array_mut) {
+        items.sort_by_cached_key(canonicalize);
+    }
+}
+
+fn write_value(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if n.is_f64() => out.push_str(&format_number(f)),
+            _ => out.push_str(&n.to_string()),
+        },
+        Value::String(s) => write_string(s, out),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(key, out);
+                out.push(':');
+                write_value(item, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0c}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// ECMAScript `Number.prototype.toString` for a finite double.
+pub fn format_number(value: f64) -> String {
+    if value == 0.0 {
+        return "0".to_string();
+    }
+
+    // Shortest round-trip digits and exponent, e.g. "1.25e-7"
+    let scientific = format!("{:e}", value.abs());
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("{:e} always has an exponent");
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let exponent: i32 = exponent.parse().expect("{:e} exponent is an integer");
+
+    let k = digits.len() as i32;
+    let n = exponent + 1;
+    let body = if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat((-n) as usize), digits)
+    } else {
+        let fraction = if k > 1 {
+            format!(".{}", &digits[1..])
+        } else {
+            String::new()
+        };
+        let sign = if n - 1 < 0 { '-' } else { '+' };
+        format!("{}{}e{}{}", &digits[..1], fraction, sign, (n - 1).abs())
+    };
+
+    if value < 0.0 {
+        format!("-{}", body)
+    } else {
+        body
+    }
+}
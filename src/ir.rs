@@ -0,0 +1,201 @@
+//! Intermediate representation (IR) of a compiled identity plan.
+//!
+//! The IR is self-contained: every default is filled in and every rule is
+//! linked to the source columns it reads, so an engine can execute the plan
+//! without access to the original spec.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::spec::{Spec, DEFAULT_AGGREGATION, DEFAULT_BLOCKING_STRATEGY};
+
+/// Layout version written to `ir_version`. Bump on any change an older
+/// runtime could misread; `schemas/kanoniv-ir-v<N>.schema.json` describes it.
+pub const IR_VERSION: &str = "1";
+
+/// Keys that document a spec but never change how it executes.
+const COSMETIC_KEYS: &[&str] = &["description"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    pub ir_version: String,
+    pub api_version: String,
+    pub identity_version: String,
+    pub entity: String,
+    pub sources: Vec<PlanSource>,
+    pub rules: Vec<PlanRule>,
+    pub blocking: PlanBlocking,
+    pub decision: PlanDecision,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub plan_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanSource {
+    pub name: String,
+    pub system: String,
+    pub table: String,
+    pub id: String,
+    /// Canonical attribute name -> column in `table`
+    pub attributes: BTreeMap<String, String>,
+    /// Hash of this component; see [`crate::hashing`]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanRule {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub rule_type: String,
+    pub field: Option<String>,
+    pub weight: f64,
+    pub threshold: f64,
+    pub params: BTreeMap<String, Value>,
+    /// Source columns feeding `field`, one per source that maps it
+    pub inputs: Vec<RuleInput>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleInput {
+    pub source: String,
+    pub column: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanBlocking {
+    pub strategy: String,
+    pub keys: Vec<String>,
+    pub params: BTreeMap<String, Value>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanDecision {
+    pub aggregation: String,
+    pub thresholds: PlanThresholds,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlanThresholds {
+    #[serde(rename = "match")]
+    pub match_: f64,
+    pub review: f64,
+    pub reject: f64,
+}
+
+/// Lower a spec into a [`Plan`]. `plan_hash` and the component hashes are
+/// left empty.
+///
+/// The spec is expected to have passed validation; missing required fields
+/// are reported as errors rather than defaulted.
+pub fn lower(spec: &Spec) -> Result<Plan> {
+    let required = |value: Option<&String>, what: &str| {
+        value
+            .cloned()
+            .ok_or_else(|| anyhow!("Cannot compile spec: missing {}", what))
+    };
+
+    let sources = spec
+        .sources
+        .iter()
+        .enumerate()
+        .map(|(i, source)| {
+            Ok(PlanSource {
+                name: required(source.name.as_ref(), &format!("sources[{}].name", i))?,
+                system: required(source.system.as_ref(), &format!("sources[{}].system", i))?,
+                table: required(source.table.as_ref(), &format!("sources[{}].table", i))?,
+                id: required(source.id.as_ref(), &format!("sources[{}].id", i))?,
+                attributes: source.attributes.clone().unwrap_or_default(),
+                hash: String::new(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let rules = spec
+        .rules
+        .iter()
+        .enumerate()
+        .map(|(i, rule)| {
+            let inputs = rule
+                .field
+                .as_ref()
+                .map(|field| {
+                    sources
+                        .iter()
+                        .filter_map(|source| {
+                            source.attributes.get(field).map(|column| RuleInput {
+                                source: source.name.clone(),
+                                column: column.clone(),
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            Ok(PlanRule {
+                name: required(rule.name.as_ref(), &format!("rules[{}].name", i))?,
+                rule_type: required(rule.rule_type.as_ref(), &format!("rules[{}].type", i))?,
+                field: rule.field.clone(),
+                weight: rule.weight_or_default(),
+                threshold: rule.threshold_or_default(),
+                params: without_cosmetic(&rule.params_or_default()),
+                inputs,
+                hash: String::new(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let blocking = spec.blocking.clone().unwrap_or_default();
+    let blocking_params = blocking.params_or_default();
+    let thresholds = spec.thresholds().copied().unwrap_or_default();
+
+    Ok(Plan {
+        ir_version: IR_VERSION.to_string(),
+        api_version: required(spec.api_version.as_ref(), "api_version")?,
+        identity_version: required(spec.identity_version.as_ref(), "identity_version")?,
+        entity: spec
+            .entity_name()
+            .map(String::from)
+            .ok_or_else(|| anyhow!("Cannot compile spec: missing entity.name"))?,
+        sources,
+        rules,
+        blocking: PlanBlocking {
+            strategy: blocking
+                .strategy
+                .unwrap_or_else(|| DEFAULT_BLOCKING_STRATEGY.to_string()),
+            keys: blocking.keys,
+            params: without_cosmetic(&blocking_params),
+            hash: String::new(),
+        },
+        decision: PlanDecision {
+            aggregation: spec
+                .decision
+                .as_ref()
+                .and_then(|d| d.aggregation.clone())
+                .unwrap_or_else(|| DEFAULT_AGGREGATION.to_string()),
+            thresholds: PlanThresholds {
+                match_: thresholds.match_or_default(),
+                review: thresholds.review_or_default(),
+                reject: thresholds.reject_or_default(),
+            },
+            hash: String::new(),
+        },
+        plan_hash: String::new(),
+    })
+}
+
+fn without_cosmetic(params: &BTreeMap<String, Value>) -> BTreeMap<String, Value> {
+    params
+        .iter()
+        .filter(|(key, _)| !COSMETIC_KEYS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
@@ -3,19 +3,67 @@
 //! This module re-exports the core validation, compilation, hashing,
 //! and diffing functions for use by other Rust crates (including PyO3 bindings).
 
-pub mod validator;
-pub mod parser;
+pub mod blocking;
 pub mod commands;
+pub mod diagnostic;
+pub mod git;
+pub mod hashing;
+pub mod ir;
+pub mod lint;
+pub mod lockfile;
+pub mod merge;
+pub mod parser;
+pub mod patch;
+pub mod rule_types;
+pub mod schema;
+pub mod scoring;
+pub mod spec;
+pub mod validator;
+pub mod versioning;
 
 // Re-export the primary public functions
-pub use validator::{validate_schema, validate_semantics};
-pub use parser::parse_yaml;
 pub use commands::diff::{compute_diff, DiffResult as RustDiffResult};
+pub use diagnostic::{Code, Diagnostic, Severity};
+pub use hashing::{canonicalize, component_hash, hash_plan, plan_hash, strict_hash};
+pub use ir::{
+    Plan, PlanBlocking, PlanDecision, PlanRule, PlanSource, PlanThresholds, RuleInput, IR_VERSION,
+};
+pub use parser::{parse_yaml, parse_yaml_with_spans, SourceMap, Span};
+pub use spec::{Blocking, Decision, Entity, Rule, Source, Spec, Thresholds};
+pub use validator::{validate_schema, validate_semantics};
+pub use versioning::{check_bump, Version, VersionScheme};
+
+/// Validate a YAML string and return all diagnostics, including lint
+/// warnings and info, located in the source.
+pub fn validate_yaml_diagnostics(yaml: &str) -> anyhow::Result<Vec<Diagnostic>> {
+    let document = parser::parse_value(yaml)?;
+    let source_map = SourceMap::from_yaml(yaml)?;
+
+    // Semantic checks need a well-formed document, and lints a valid spec
+    let mut diagnostics = schema::validate_document(&document)?;
+    if diagnostics.iter().all(|d| !d.is_error()) {
+        let spec: Spec = serde_json::from_value(document)?;
+        diagnostics.extend(validate_semantics(&spec)?);
+        if diagnostics.iter().all(|d| !d.is_error()) {
+            diagnostics.extend(lint::run(&spec, &lint::LintOptions::default()));
+        }
+    }
+    Ok(diagnostics
+        .into_iter()
+        .map(|d| d.locate(&source_map))
+        .collect())
+}
 
 /// Convenience: validate a YAML string and return all errors.
+///
+/// Kept for compatibility; prefer [`validate_yaml_diagnostics`].
 pub fn validate_yaml(yaml: &str) -> anyhow::Result<Vec<String>> {
-    let spec = parse_yaml(yaml)?;
-    let mut errors = validate_schema(&spec)?;
-    errors.extend(validate_semantics(&spec)?);
-    Ok(errors)
+    Ok(validate_yaml_diagnostics(yaml)?
+        .into_iter()
+        .filter(Diagnostic::is_error)
+        .map(|d| match d.help {
+            Some(help) => format!("{} {}", d.message, help),
+            None => d.message,
+        })
+        .collect())
 }
@@ -0,0 +1,331 @@
+//! Lints: advice about specs that are valid but probably not what was meant.
+//!
+//! Each lint is a diagnostic [`Code`] in the `K02xx` range with a default
+//! severity of warning or info. Lints never fail validation on their own;
+//! `--deny-warnings` turns warnings into failures for CI, and `--allow`
+//! switches a lint off by code or name.
+
+use anyhow::{anyhow, Result};
+
+use crate::blocking;
+use crate::diagnostic::{did_you_mean, Code, Diagnostic, Severity};
+use crate::rule_types;
+use crate::scoring::{self, round_score, Aggregation};
+use crate::spec::Spec;
+
+/// A named lint and the severity it reports at.
+#[derive(Debug, Clone, Copy)]
+pub struct Lint {
+    pub code: Code,
+    pub severity: Severity,
+    pub description: &'static str,
+}
+
+pub const LINTS: &[Lint] = &[
+    Lint {
+        code: Code::ZeroWeight,
+        severity: Severity::Warning,
+        description: "A rule with weight 0 never changes the score",
+    },
+    Lint {
+        code: Code::IncompleteThresholds,
+        severity: Severity::Info,
+        description: "Only `match` is set, so there is no review band",
+    },
+    Lint {
+        code: Code::UnusedAttribute,
+        severity: Severity::Info,
+        description: "A source attribute no rule or blocking key reads",
+    },
+    Lint {
+        code: Code::UnknownBlockingKey,
+        severity: Severity::Warning,
+        description: "A blocking key no source maps",
+    },
+    Lint {
+        code: Code::IneffectiveThreshold,
+        severity: Severity::Warning,
+        description: "A threshold every pair's score clears, or none falls below",
+    },
+    Lint {
+        code: Code::UnreachableThreshold,
+        severity: Severity::Warning,
+        description: "A `match` threshold above the highest possible score",
+    },
+    Lint {
+        code: Code::IgnoredParameter,
+        severity: Severity::Warning,
+        description: "A rule or blocking parameter (or `threshold`) its type does not use",
+    },
+    Lint {
+        code: Code::ImplicitParameter,
+        severity: Severity::Info,
+        description: "A parameter left to its default, such as fuzzy without `algorithm`",
+    },
+];
+
+/// Which lints run and whether warnings fail.
+#[derive(Debug, Clone, Default)]
+pub struct LintOptions {
+    /// Lints switched off
+    pub allow: Vec<Code>,
+    /// Treat warnings as failures
+    pub deny_warnings: bool,
+}
+
+impl LintOptions {
+    /// Build options from lint names or codes given on the command line.
+    pub fn new(allow: &[String], deny_warnings: bool) -> Result<Self> {
+        let allow = allow
+            .iter()
+            .map(|name| {
+                Code::parse(name)
+                    .filter(|code| lint(*code).is_some())
+                    .ok_or_else(|| anyhow!("Unknown lint '{}'", name))
+            })
+            .collect::<Result<_>>()?;
+        Ok(LintOptions {
+            allow,
+            deny_warnings,
+        })
+    }
+
+    /// Whether `diagnostics` contain anything that fails under these options.
+    pub fn denies(&self, diagnostics: &[Diagnostic]) -> bool {
+        self.deny_warnings && diagnostics.iter().any(|d| d.severity == Severity::Warning)
+    }
+}
+
+/// The lint registered for `code`, if any.
+pub fn lint(code: Code) -> Option<&'static Lint> {
+    LINTS.iter().find(|lint| lint.code == code)
+}
+
+/// Run every lint that is not allowed.
+pub fn run(spec: &Spec, options: &LintOptions) -> Vec<Diagnostic> {
+    let mut findings = Vec::new();
+    let mut report = |code: Code, path: String, message: String, help: &str| {
+        if options.allow.contains(&code) {
+            return;
+        }
+        let severity = lint(code).map_or(Severity::Warning, |lint| lint.severity);
+        findings.push(Diagnostic::new(code, severity, path, message).with_help(help));
+    };
+
+    for (i, rule) in spec.rules.iter().enumerate() {
+        if rule.weight == Some(0.0) {
+            report(
+                Code::ZeroWeight,
+                format!("rules[{}].weight", i),
+                format!(
+                    "Rule '{}' has weight 0 and never changes the score",
+                    rule.name.as_deref().unwrap_or("unknown")
+                ),
+                "Remove the rule or give it a weight above 0",
+            );
+        }
+
+        let Some(rule_type) = rule.rule_type.as_deref().and_then(rule_types::lookup) else {
+            continue;
+        };
+        let rule_name = rule.name.as_deref().unwrap_or("unknown");
+        if rule.threshold.is_some() && !rule_type.uses_threshold {
+            report(
+                Code::IgnoredParameter,
+                format!("rules[{}].threshold", i),
+                format!(
+                    "Rule '{}' sets 'threshold', but {} rules only agree or disagree",
+                    rule_name, rule_type.name
+                ),
+                "Remove 'threshold'",
+            );
+        }
+        for key in rule_types::unknown_params(&rule.params, rule_type.params) {
+            report(
+                Code::IgnoredParameter,
+                format!("rules[{}].{}", i, key),
+                format!(
+                    "Rule '{}' sets '{}', which {} rules do not use",
+                    rule_name, key, rule_type.name
+                ),
+                "Remove it, or check the rule type",
+            );
+        }
+        for (param, default) in rule_types::implicit_params(&rule.params, rule_type.params) {
+            report(
+                Code::ImplicitParameter,
+                format!("rules[{}]", i),
+                format!(
+                    "Rule '{}' ({}) does not set '{}', so '{}' is used",
+                    rule_name, rule_type.name, param.name, default
+                ),
+                &format!(
+                    "Set '{}: {}' to make the choice explicit",
+                    param.name, default
+                ),
+            );
+        }
+    }
+
+    if let Some(thresholds) = spec.thresholds() {
+        if thresholds.match_.is_some() && thresholds.review.is_none() && thresholds.reject.is_none()
+        {
+            report(
+                Code::IncompleteThresholds,
+                "decision.thresholds".to_string(),
+                "Only 'match' is set, so 'review' defaults to it and no pair goes to review"
+                    .to_string(),
+                "Set 'review' (and 'reject') to get a review band",
+            );
+        }
+    }
+
+    // Thresholds the score range makes unreachable or pointless
+    if let Some(thresholds) = spec.thresholds().filter(|_| !spec.rules.is_empty()) {
+        let range = scoring::score_range(spec);
+        let lowest = round_score(range.min);
+        let match_t = thresholds.match_or_default();
+        if !range.reaches(match_t) {
+            let (path, threshold) = match thresholds.match_ {
+                Some(_) => ("decision.thresholds.match", format!("'match: {}'", match_t)),
+                None => (
+                    "decision.thresholds",
+                    format!("'match' (default {})", match_t),
+                ),
+            };
+            report(
+                Code::UnreachableThreshold,
+                path.to_string(),
+                format!(
+                    "{} cannot be reached because {}, so no pair can ever match",
+                    threshold,
+                    best_score(spec, range.max)
+                ),
+                &format!(
+                    "Lower 'match' to at most {}, or raise the rule weights",
+                    round_score(range.max)
+                ),
+            );
+        } else if let Some(match_t) = thresholds.match_.filter(|t| range.always_reaches(*t)) {
+            report(
+                Code::IneffectiveThreshold,
+                "decision.thresholds.match".to_string(),
+                format!(
+                    "'match: {}' is at or below the minimum possible score {}, so every pair matches",
+                    match_t, lowest
+                ),
+                "Raise 'match' above the score of a pair no rule agrees on",
+            );
+        } else if let Some(review) = thresholds.review.filter(|t| range.always_reaches(*t)) {
+            report(
+                Code::IneffectiveThreshold,
+                "decision.thresholds.review".to_string(),
+                format!(
+                    "'review: {}' is at or below the minimum possible score {}, so no pair is rejected",
+                    review, lowest
+                ),
+                "Raise 'review' above the score of a pair no rule agrees on",
+            );
+        }
+        if let Some(reject) = thresholds.reject.filter(|t| *t < lowest) {
+            report(
+                Code::IneffectiveThreshold,
+                "decision.thresholds.reject".to_string(),
+                format!(
+                    "'reject: {}' is below the minimum possible score {}, so no pair scores under it",
+                    reject, lowest
+                ),
+                "Raise 'reject' to at least the minimum possible score, or remove it",
+            );
+        }
+    }
+
+    let blocking_keys = spec
+        .blocking
+        .as_ref()
+        .map(|b| b.keys.as_slice())
+        .unwrap_or_default();
+    let used = |attribute: &str| {
+        spec.rules
+            .iter()
+            .any(|rule| rule.field.as_deref() == Some(attribute))
+            || blocking_keys.iter().any(|key| key == attribute)
+    };
+    for (i, source) in spec.sources.iter().enumerate() {
+        for attribute in source.attributes.iter().flat_map(|a| a.keys()) {
+            if !used(attribute) {
+                report(
+                    Code::UnusedAttribute,
+                    format!("sources[{}].attributes.{}", i, attribute),
+                    format!(
+                        "Attribute '{}' of source '{}' is not used by any rule or blocking key",
+                        attribute,
+                        source.name.as_deref().unwrap_or("unknown")
+                    ),
+                    "Remove the mapping, or add a rule that compares it",
+                );
+            }
+        }
+    }
+
+    // Keys that look like a typo of an attribute are errors in
+    // `validate_semantics`
+    let available_fields = spec.available_fields();
+    for (i, key) in blocking_keys.iter().enumerate() {
+        if !available_fields.contains(&key.as_str())
+            && did_you_mean(key, available_fields.iter().copied()).is_none()
+        {
+            report(
+                Code::UnknownBlockingKey,
+                format!("blocking.keys[{}]", i),
+                format!("Blocking key '{}' is not mapped by any source", key),
+                "Map the attribute in a source, or remove the key",
+            );
+        }
+    }
+
+    let strategy = spec.blocking.as_ref().and_then(|blocking| {
+        blocking::lookup(blocking::strategy_name(blocking)).map(|strategy| (blocking, strategy))
+    });
+    if let Some((blocking, strategy)) = strategy {
+        for key in rule_types::unknown_params(&blocking.params, strategy.params) {
+            report(
+                Code::IgnoredParameter,
+                format!("blocking.{}", key),
+                format!(
+                    "Blocking sets '{}', which the {} strategy does not use",
+                    key, strategy.name
+                ),
+                "Remove it, or check the strategy",
+            );
+        }
+        for (param, default) in rule_types::implicit_params(&blocking.params, strategy.params) {
+            report(
+                Code::ImplicitParameter,
+                "blocking".to_string(),
+                format!(
+                    "Blocking strategy '{}' does not set '{}', so '{}' is used",
+                    strategy.name, param.name, default
+                ),
+                &format!(
+                    "Set '{}: {}' to make the choice explicit",
+                    param.name, default
+                ),
+            );
+        }
+    }
+
+    findings
+}
+
+/// Why `max` is the highest score, in the terms of the aggregation.
+fn best_score(spec: &Spec, max: f64) -> String {
+    let max = round_score(max);
+    match Aggregation::of(spec) {
+        Aggregation::WeightedSum => format!("the rule weights sum to {}", max),
+        Aggregation::Max => format!("the largest rule weight is {}", max),
+        Aggregation::WeightedAverage => {
+            format!("the highest weighted_average score is {}", max)
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! `kanoniv.lock`: the expected plan hash of each spec in a repository.
+//!
+//! The lock is committed next to the specs. Changing a spec's plan without
+//! updating the lock fails `kanoniv hash --locked`, so every intended change
+//! to an identity plan shows up as a lock diff in review.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{self, Component, Path, PathBuf};
+
+/// File name used when no `--lockfile` is given.
+pub const DEFAULT_LOCKFILE: &str = "kanoniv.lock";
+
+/// Layout version written to `version`.
+pub const LOCKFILE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub version: u32,
+    /// Spec path (relative to the lockfile's directory, `/`-separated) ->
+    /// plan hash
+    pub specs: BTreeMap<String, String>,
+    /// Directory the lockfile lives in; spec paths are keyed relative to it
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+impl Default for Lockfile {
+    fn default() -> Self {
+        Lockfile {
+            version: LOCKFILE_VERSION,
+            specs: BTreeMap::new(),
+            dir: PathBuf::new(),
+        }
+    }
+}
+
+impl Lockfile {
+    /// Read a lockfile; a missing file is an empty lock.
+    pub fn load(path: &Path) -> Result<Lockfile> {
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        if !path.exists() {
+            return Ok(Lockfile {
+                dir,
+                ..Lockfile::default()
+            });
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        let mut lock: Lockfile = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse lockfile: {}", path.display()))?;
+        if lock.version != LOCKFILE_VERSION {
+            return Err(anyhow!(
+                "Unsupported lockfile version {} in {} (expected {})",
+                lock.version,
+                path.display(),
+                LOCKFILE_VERSION
+            ));
+        }
+        lock.dir = dir;
+        Ok(lock)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Failed to write file: {}", path.display()))
+    }
+
+    pub fn get(&self, spec: &Path) -> Result<Option<&str>> {
+        Ok(self.specs.get(&self.key(spec)?).map(String::as_str))
+    }
+
+    pub fn insert(&mut self, spec: &Path, hash: String) -> Result<()> {
+        self.specs.insert(self.key(spec)?, hash);
+        Ok(())
+    }
+
+    /// Lock key for a spec path, the same from any working directory: with
+    /// the lock in `repo/`, `repo/specs/a.yaml` (or `specs\a.yaml` run from
+    /// `repo` on Windows) is `specs/a.yaml`.
+    fn key(&self, spec: &Path) -> Result<String> {
+        let dir = absolute(&self.dir)?;
+        let spec = absolute(spec)?;
+        let common = dir
+            .iter()
+            .zip(spec.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let parents = (common..dir.len()).map(|_| "..".into());
+        let rest = spec[common..].iter().cloned();
+        Ok(parents.chain(rest).collect::<Vec<String>>().join("/"))
+    }
+}
+
+/// `path` made absolute, with `.` and `..` resolved lexically.
+fn absolute(path: &Path) -> Result<Vec<String>> {
+    let path = if path.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        path
+    };
+    let path = path::absolute(path)
+        .with_context(|| format!("Failed to resolve path: {}", path.display()))?;
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                components.pop();
+            }
+            other => components.push(other.as_os_str().to_string_lossy().into_owned()),
+        }
+    }
+    Ok(components)
+}
@@ -2,9 +2,13 @@ use clap::{Parser, Subcommand};
 use colored::Colorize;
 use std::path::PathBuf;
 
-mod commands;
-mod parser;
-mod validator;
+use kanoniv_core::commands;
+use kanoniv_core::lint::LintOptions;
+use kanoniv_core::lockfile::DEFAULT_LOCKFILE;
+use kanoniv_core::versioning::VersionScheme;
+
+/// Values accepted by `--version-scheme`.
+const VERSION_SCHEMES: [&str; 2] = ["any", "major-minor"];
 
 #[derive(Parser)]
 #[command(name = "kanoniv")]
@@ -18,15 +22,39 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Validate a Kanoniv identity specification
+    /// Validate one or more Kanoniv identity specifications
     Validate {
-        /// Path to the YAML file
-        #[arg(value_name = "FILE")]
-        file: PathBuf,
+        /// YAML files or directories to validate (`-` reads from stdin)
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<PathBuf>,
 
         /// Output format (text, json)
         #[arg(short, long, default_value = "text")]
         format: String,
+
+        /// Glob of files to pick up when walking directories (default: *.yaml, *.yml)
+        #[arg(long, value_name = "GLOB")]
+        include: Vec<String>,
+
+        /// Glob of files or directories to skip
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+
+        /// Also require an identity_version bump for any plan change since this git revision
+        #[arg(long, visible_alias = "base", value_name = "REV")]
+        against: Option<String>,
+
+        /// How identity_version bumps are judged with --against
+        #[arg(long, value_name = "SCHEME", default_value = "any", value_parser = VERSION_SCHEMES)]
+        version_scheme: String,
+
+        /// Fail when any lint reports a warning
+        #[arg(long)]
+        deny_warnings: bool,
+
+        /// Switch off a lint, by name or code (e.g. unused-attribute, K0203)
+        #[arg(long, value_name = "LINT")]
+        allow: Vec<String>,
     },
 
     /// Compile a specification to intermediate representation
@@ -40,33 +68,308 @@ enum Commands {
         output: Option<PathBuf>,
     },
 
-    /// Compute the plan hash for a specification
+    /// Work with compiled plans (intermediate representation)
+    Ir {
+        #[command(subcommand)]
+        command: IrCommands,
+    },
+
+    /// Compute the plan hash for one or more specifications
     Hash {
-        /// Path to the YAML file
-        #[arg(value_name = "FILE")]
-        file: PathBuf,
+        /// Paths to the YAML files
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<PathBuf>,
+
+        /// Hash the document exactly as written instead of the compiled plan
+        #[arg(long)]
+        strict: bool,
+
+        /// Also print the hash of each source, rule, blocking and decision
+        #[arg(long, conflicts_with = "strict")]
+        tree: bool,
+
+        /// Fail unless the spec hashes to this value (sha256:...)
+        #[arg(long, value_name = "HASH", conflicts_with_all = ["locked", "update_lock"])]
+        check: Option<String>,
+
+        /// Fail unless every spec matches its hash in the lockfile
+        #[arg(long, conflicts_with_all = ["update_lock", "strict"])]
+        locked: bool,
+
+        /// Record the hash of every spec in the lockfile
+        #[arg(long, conflicts_with = "strict")]
+        update_lock: bool,
+
+        /// Lockfile used by --locked and --update-lock
+        #[arg(long, value_name = "PATH", default_value = DEFAULT_LOCKFILE)]
+        lockfile: PathBuf,
     },
 
     /// Compare two specification versions
     Diff {
-        /// First version
-        #[arg(value_name = "FILE1")]
-        file1: PathBuf,
+        /// Old and new version, one spec with --against, or paths to search with --git
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
+
+        /// Compare the spec against its content at this git revision
+        #[arg(
+            long,
+            visible_alias = "base",
+            value_name = "REV",
+            conflicts_with = "git"
+        )]
+        against: Option<String>,
+
+        /// Compare every spec that changed in a git range (base..head, base...head or base)
+        #[arg(long, value_name = "RANGE")]
+        git: Option<String>,
+
+        /// Output format
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json", "markdown", "unified", "json-patch"])]
+        format: String,
+
+        /// Exit non-zero if any change has at least this impact
+        #[arg(long, value_name = "LEVEL", value_parser = ["breaking", "risky", "safe"])]
+        fail_on: Option<String>,
+
+        /// Exit non-zero if the plan changed but identity_version was not bumped
+        #[arg(long)]
+        require_version_bump: bool,
+
+        /// How identity_version bumps are judged: any new value, or major.minor
+        #[arg(long, value_name = "SCHEME", default_value = "any", value_parser = VERSION_SCHEMES)]
+        version_scheme: String,
+
+        /// Similarity (0-1) at which a removed and an added rule or source count as a rename
+        #[arg(long, value_name = "SCORE", default_value_t = commands::diff::DEFAULT_RENAME_THRESHOLD, value_parser = parse_score)]
+        rename_threshold: f64,
+    },
 
-        /// Second version
-        #[arg(value_name = "FILE2")]
-        file2: PathBuf,
+    /// Three-way merge of two versions of a specification
+    Merge {
+        /// Common ancestor
+        #[arg(value_name = "BASE")]
+        base: PathBuf,
+
+        /// Our version
+        #[arg(value_name = "OURS")]
+        ours: PathBuf,
+
+        /// Their version
+        #[arg(value_name = "THEIRS")]
+        theirs: PathBuf,
+
+        /// Output file path (default: print the merged spec)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
+
+    /// Apply a JSON Patch (RFC 6902) to a specification and re-validate it
+    Patch {
+        /// Path to the YAML spec
+        #[arg(value_name = "SPEC")]
+        spec: PathBuf,
+
+        /// JSON file with the patch operations
+        #[arg(value_name = "PATCH")]
+        patch: PathBuf,
+
+        /// Output file path (default: print the patched spec)
+        #[arg(short, long, conflicts_with = "in_place")]
+        output: Option<PathBuf>,
+
+        /// Overwrite the spec with the patched version
+        #[arg(short, long)]
+        in_place: bool,
+    },
+
+    /// Explain how a spec scores a pair of records
+    Explain {
+        /// Path to the YAML spec
+        #[arg(value_name = "SPEC")]
+        spec: PathBuf,
+
+        /// JSON file with the record pair ({"left": {...}, "right": {...}})
+        #[arg(value_name = "RECORDS")]
+        records: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Show which rules the blocking keys cover
+    Coverage {
+        /// Path to the YAML spec
+        #[arg(value_name = "SPEC")]
+        spec: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Print the JSON Schema for a spec API version, or wire it into editors
+    Schema {
+        /// API version to export
+        #[arg(long, default_value = kanoniv_core::schema::LATEST_API_VERSION)]
+        api_version: String,
+
+        /// Write the schema to this file instead of printing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Add a yaml-language-server modeline pointing at the schema to these specs
+        #[arg(long, value_name = "FILE", num_args = 1.., requires = "output")]
+        modeline: Vec<PathBuf>,
+
+        /// Map this glob to the schema in .vscode/settings.json
+        #[arg(long, value_name = "GLOB", requires = "output")]
+        vscode: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum IrCommands {
+    /// Check compiled plans against the IR schema and their plan hash
+    Validate {
+        /// Compiled plan files (JSON)
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Print the JSON Schema for compiled plans
+    Schema,
+}
+
+/// Parse a similarity score between 0 and 1.
+fn parse_score(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(score) if (0.0..=1.0).contains(&score) => Ok(score),
+        Ok(_) => Err("must be between 0 and 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Work out what `kanoniv diff` compares from its arguments.
+fn diff_source<'a>(
+    paths: &'a [PathBuf],
+    against: Option<&'a str>,
+    git: Option<&'a str>,
+) -> anyhow::Result<commands::diff::DiffSource<'a>> {
+    use commands::diff::DiffSource;
+
+    match (against, git, paths) {
+        (_, Some(range), paths) => Ok(DiffSource::GitRange(range, paths)),
+        (Some(rev), None, [file]) => Ok(DiffSource::Against(file, rev)),
+        (Some(_), None, _) => Err(anyhow::anyhow!("--against takes exactly one spec file")),
+        (None, None, [file1, file2]) => Ok(DiffSource::Files(file1, file2)),
+        (None, None, _) => Err(anyhow::anyhow!(
+            "diff takes two spec files, or one with --against REV, or --git RANGE"
+        )),
+    }
 }
 
 fn main() {
     let cli = Cli::parse();
 
     let result = match cli.command {
-        Commands::Validate { file, format } => commands::validate::run(&file, &format),
+        Commands::Validate {
+            paths,
+            format,
+            include,
+            exclude,
+            against,
+            version_scheme,
+            deny_warnings,
+            allow,
+        } => LintOptions::new(&allow, deny_warnings).and_then(|lints| {
+            commands::validate::run(
+                &paths,
+                &format,
+                &include,
+                &exclude,
+                against
+                    .as_deref()
+                    .zip(VersionScheme::parse(&version_scheme)),
+                &lints,
+            )
+        }),
         Commands::Compile { file, output } => commands::compile::run(&file, output.as_deref()),
-        Commands::Hash { file } => commands::hash::run(&file),
-        Commands::Diff { file1, file2 } => commands::diff::run(&file1, &file2),
+        Commands::Ir { command } => match command {
+            IrCommands::Validate { files, format } => commands::ir::validate(&files, &format),
+            IrCommands::Schema => commands::ir::schema(),
+        },
+        Commands::Hash {
+            files,
+            strict,
+            tree,
+            check,
+            locked,
+            update_lock,
+            lockfile,
+        } => commands::hash::run(
+            &files,
+            &commands::hash::HashOptions {
+                strict,
+                tree,
+                check: check.as_deref(),
+                lockfile: &lockfile,
+                locked,
+                update_lock,
+            },
+        ),
+        Commands::Diff {
+            paths,
+            against,
+            git,
+            format,
+            fail_on,
+            require_version_bump,
+            version_scheme,
+            rename_threshold,
+        } => diff_source(&paths, against.as_deref(), git.as_deref()).and_then(|source| {
+            commands::diff::run(
+                source,
+                &format,
+                fail_on.as_deref().and_then(commands::diff::Impact::parse),
+                VersionScheme::parse(&version_scheme).filter(|_| require_version_bump),
+                &commands::diff::DiffOptions { rename_threshold },
+            )
+        }),
+        Commands::Merge {
+            base,
+            ours,
+            theirs,
+            output,
+        } => commands::merge::run(&base, &ours, &theirs, output.as_deref()),
+        Commands::Patch {
+            spec,
+            patch,
+            output,
+            in_place,
+        } => commands::patch::run(&spec, &patch, output.as_deref(), in_place),
+        Commands::Explain {
+            spec,
+            records,
+            format,
+        } => commands::explain::run(&spec, &records, &format),
+        Commands::Coverage { spec, format } => commands::coverage::run(&spec, &format),
+        Commands::Schema {
+            api_version,
+            output,
+            modeline,
+            vscode,
+        } => commands::schema::run(
+            &api_version,
+            output.as_deref(),
+            &modeline,
+            vscode.as_deref(),
+        ),
     };
 
     match result {
@@ -0,0 +1,326 @@
+//! Three-way merge of identity specs.
+//!
+//! The merge works on the spec structure rather than its lines. Rules and
+//! sources are matched by name, following renames the way
+//! [`compute_diff`](crate::commands::diff::compute_diff) detects them, and
+//! every field is merged on its own: a change on one side wins over the
+//! base, equal changes on both sides agree, and different changes to the
+//! same field are a conflict. Lists of plain values (such as
+//! `blocking.keys`) merge as sets.
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::commands::diff::{compute_diff, Rename};
+use crate::parser;
+use crate::patch::{self, Patched};
+
+/// Top-level lists whose items are matched by `name`, with the component
+/// label used in conflicts.
+const NAMED_SECTIONS: &[(&str, &str)] = &[("rules", "rule"), ("sources", "source")];
+
+/// A field both sides changed differently. Missing values are `None`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Conflict {
+    /// `rule email_exact`, `source crm`, `decision`, `spec`, ...
+    pub component: String,
+    /// Path inside the component (`weight`, `thresholds.match`), empty when
+    /// the whole component is in conflict
+    pub field: String,
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+/// Result of a merge. Conflicted fields keep our value.
+#[derive(Debug, Clone)]
+pub struct MergeResult {
+    /// The merged spec, laid out like ours where possible
+    pub content: String,
+    /// Whether our comments and formatting were kept
+    pub preserved: bool,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl MergeResult {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Merge the changes from `base` to `ours` and from `base` to `theirs`.
+pub fn merge(base: &str, ours: &str, theirs: &str) -> Result<MergeResult> {
+    let base_doc = parser::parse_value(base)?;
+    let ours_doc = parser::parse_value(ours)?;
+    let theirs_doc = parser::parse_value(theirs)?;
+
+    let mut merger = Merger {
+        ours_renames: renames(base, ours)?,
+        theirs_renames: renames(base, theirs)?,
+        conflicts: Vec::new(),
+    };
+    let merged = merger
+        .value(
+            &mut Vec::new(),
+            Some(&base_doc),
+            Some(&ours_doc),
+            Some(&theirs_doc),
+        )
+        .unwrap_or(Value::Null);
+
+    // Apply the result to our text so our comments and layout survive
+    // where they can; what only they added keeps their key order
+    let Patched { content, preserved } =
+        patch::apply_with_layouts(ours, &json_patch::diff(&ours_doc, &merged), &[theirs, base])?;
+    Ok(MergeResult {
+        content,
+        preserved,
+        conflicts: merger.conflicts,
+    })
+}
+
+/// Per named section, new name -> base name for every rename on one side.
+fn renames(base: &str, side: &str) -> Result<HashMap<&'static str, BTreeMap<String, String>>> {
+    let diff = compute_diff(base, side)?;
+    let invert = |renamed: Vec<Rename>| {
+        renamed
+            .into_iter()
+            .map(|r| (r.new_name, r.old_name))
+            .collect::<BTreeMap<_, _>>()
+    };
+    Ok(HashMap::from([
+        ("rules", invert(diff.rules_renamed)),
+        ("sources", invert(diff.sources_renamed)),
+    ]))
+}
+
+struct Merger {
+    ours_renames: HashMap<&'static str, BTreeMap<String, String>>,
+    theirs_renames: HashMap<&'static str, BTreeMap<String, String>>,
+    conflicts: Vec<Conflict>,
+}
+
+impl Merger {
+    /// Merge one node; `None` means absent (or deleted) on that side.
+    fn value(
+        &mut self,
+        path: &mut Vec<String>,
+        base: Option<&Value>,
+        ours: Option<&Value>,
+        theirs: Option<&Value>,
+    ) -> Option<Value> {
+        if same(ours, theirs) || same(theirs, base) {
+            return ours.cloned();
+        }
+        if same(ours, base) {
+            return theirs.cloned();
+        }
+
+        match (ours, theirs) {
+            (Some(Value::Object(o)), Some(Value::Object(t))) => {
+                let empty = Map::new();
+                let b = base.and_then(Value::as_object).unwrap_or(&empty);
+                Some(Value::Object(self.object(path, b, o, t)))
+            }
+            (Some(Value::Array(o)), Some(Value::Array(t))) => {
+                let empty = Vec::new();
+                let b = base.and_then(Value::as_array).unwrap_or(&empty);
+                if let Some(section) = self.named_section(path, b, o, t) {
+                    Some(Value::Array(self.named(path, section, b, o, t)))
+                } else if [b, o, t].iter().all(|items| items.iter().all(is_scalar)) {
+                    Some(Value::Array(merge_sets(b, o, t)))
+                } else {
+                    self.conflict(path, base, ours, theirs)
+                }
+            }
+            _ => self.conflict(path, base, ours, theirs),
+        }
+    }
+
+    fn object(
+        &mut self,
+        path: &mut Vec<String>,
+        base: &Map<String, Value>,
+        ours: &Map<String, Value>,
+        theirs: &Map<String, Value>,
+    ) -> Map<String, Value> {
+        let mut keys: Vec<&String> = ours.keys().collect();
+        keys.extend(theirs.keys().filter(|k| !ours.contains_key(*k)));
+        keys.extend(
+            base.keys()
+                .filter(|k| !ours.contains_key(*k) && !theirs.contains_key(*k)),
+        );
+
+        let mut merged = Map::new();
+        for key in keys {
+            path.push(key.clone());
+            if let Some(value) = self.value(path, base.get(key), ours.get(key), theirs.get(key)) {
+                merged.insert(key.clone(), value);
+            }
+            path.pop();
+        }
+        merged
+    }
+
+    /// The section name if `path` is a top-level list of uniquely named items.
+    fn named_section(
+        &self,
+        path: &[String],
+        base: &[Value],
+        ours: &[Value],
+        theirs: &[Value],
+    ) -> Option<&'static str> {
+        let [key] = path else {
+            return None;
+        };
+        let section = NAMED_SECTIONS
+            .iter()
+            .map(|(section, _)| *section)
+            .find(|section| section == key)?;
+        [base, ours, theirs]
+            .iter()
+            .all(|items| {
+                let mut names: Vec<_> = items.iter().map(item_name).collect();
+                let count = names.len();
+                names.sort();
+                names.dedup();
+                names.len() == count && names.iter().all(Option::is_some)
+            })
+            .then_some(section)
+    }
+
+    /// Merge rules or sources item by item, matched through renames.
+    fn named(
+        &mut self,
+        path: &mut Vec<String>,
+        section: &'static str,
+        base: &[Value],
+        ours: &[Value],
+        theirs: &[Value],
+    ) -> Vec<Value> {
+        let identity = |renames: &HashMap<&str, BTreeMap<String, String>>, item: &Value| {
+            let name = item_name(item).unwrap_or_default().to_string();
+            renames[section].get(&name).cloned().unwrap_or(name)
+        };
+        let base_items: BTreeMap<String, &Value> = base
+            .iter()
+            .map(|item| (item_name(item).unwrap_or_default().to_string(), item))
+            .collect();
+        let ours_ids: Vec<String> = ours
+            .iter()
+            .map(|item| identity(&self.ours_renames, item))
+            .collect();
+        let theirs_ids: Vec<String> = theirs
+            .iter()
+            .map(|item| identity(&self.theirs_renames, item))
+            .collect();
+        // Our order first; items only they have go after their predecessor
+        let mut merged: Vec<(String, Value)> = Vec::new();
+        for (id, item) in ours_ids.iter().zip(ours) {
+            path.push(item_name(item).unwrap_or(id).to_string());
+            let value = self.value(
+                path,
+                base_items.get(id).copied(),
+                Some(item),
+                theirs_ids
+                    .iter()
+                    .position(|i| i == id)
+                    .map(|position| &theirs[position]),
+            );
+            path.pop();
+            merged.extend(value.map(|value| (id.clone(), value)));
+        }
+        for (position, (id, item)) in theirs_ids.iter().zip(theirs).enumerate() {
+            if ours_ids.contains(id) {
+                continue;
+            }
+            path.push(item_name(item).unwrap_or(id).to_string());
+            let value = self.value(path, base_items.get(id).copied(), None, Some(item));
+            path.pop();
+            let Some(value) = value else {
+                continue;
+            };
+            let at = theirs_ids[..position]
+                .iter()
+                .rev()
+                .find_map(|previous| merged.iter().position(|(m, _)| m == previous))
+                .map_or(0, |i| i + 1);
+            merged.insert(at, (id.clone(), value));
+        }
+        merged.into_iter().map(|(_, value)| value).collect()
+    }
+
+    /// Record a conflict and keep our side.
+    fn conflict(
+        &mut self,
+        path: &[String],
+        base: Option<&Value>,
+        ours: Option<&Value>,
+        theirs: Option<&Value>,
+    ) -> Option<Value> {
+        let (component, field) = match path {
+            [section, name, field @ ..] if NAMED_SECTIONS.iter().any(|(s, _)| s == section) => {
+                let label = NAMED_SECTIONS
+                    .iter()
+                    .find(|(s, _)| s == section)
+                    .map_or("", |(_, label)| *label);
+                (format!("{} {}", label, name), field.join("."))
+            }
+            [section, field @ ..] if section == "blocking" || section == "decision" => {
+                (section.clone(), field.join("."))
+            }
+            _ => ("spec".to_string(), path.join(".")),
+        };
+        self.conflicts.push(Conflict {
+            component,
+            field,
+            base: base.cloned(),
+            ours: ours.cloned(),
+            theirs: theirs.cloned(),
+        });
+        ours.cloned()
+    }
+}
+
+fn item_name(item: &Value) -> Option<&str> {
+    item.get("name").and_then(Value::as_str)
+}
+
+/// Whether two nodes are equal, comparing numbers by value so that `1` and
+/// `1.0` agree.
+fn same(a: Option<&Value>, b: Option<&Value>) -> bool {
+    match (a, b) {
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a.as_f64() == b.as_f64(),
+        (Some(Value::Array(a)), Some(Value::Array(b))) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(Some(a), Some(b)))
+        }
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            a.len() == b.len() && a.iter().all(|(key, a)| same(Some(a), b.get(key)))
+        }
+        _ => a == b,
+    }
+}
+
+fn is_scalar(value: &Value) -> bool {
+    !value.is_object() && !value.is_array()
+}
+
+/// Three-way merge of lists treated as sets: keep what both kept, add what
+/// either added.
+fn merge_sets(base: &[Value], ours: &[Value], theirs: &[Value]) -> Vec<Value> {
+    let contains = |items: &[Value], item: &Value| items.iter().any(|i| same(Some(i), Some(item)));
+    let removed = |side: &[Value], item: &Value| contains(base, item) && !contains(side, item);
+    let mut merged: Vec<Value> = ours
+        .iter()
+        .filter(|item| !removed(theirs, item))
+        .cloned()
+        .collect();
+    for item in theirs {
+        if !contains(&merged, item) && !removed(ours, item) {
+            merged.push(item.clone());
+        }
+    }
+    merged
+}
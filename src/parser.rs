@@ -1,7 +1,230 @@
 use anyhow::Result;
+use serde::Serialize;
 use serde_json::Value;
+use std::collections::BTreeMap;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::{Marker, TScalarStyle};
 
-pub fn parse_yaml(content: &str) -> Result<Value> {
+use crate::spec::Spec;
+
+pub fn parse_yaml(content: &str) -> Result<Spec> {
+    let spec: Spec = serde_yaml::from_str(content)?;
+    Ok(spec)
+}
+
+/// Parse YAML into an untyped document, e.g. for schema validation.
+pub fn parse_value(content: &str) -> Result<Value> {
     let value: Value = serde_yaml::from_str(content)?;
     Ok(value)
 }
+
+/// Parse a spec and record where every node sits in the source text.
+pub fn parse_yaml_with_spans(content: &str) -> Result<(Spec, SourceMap)> {
+    let spec = parse_yaml(content)?;
+    let source_map = SourceMap::from_yaml(content)?;
+    Ok((spec, source_map))
+}
+
+/// A 1-based line/column position in a YAML document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// How a scalar is written in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarStyle {
+    Plain,
+    SingleQuoted,
+    DoubleQuoted,
+    /// A `|` or `>` block scalar
+    Block,
+}
+
+/// A scalar value and where it starts in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScalarSpan {
+    /// Character offset of the scalar's first character (its opening quote
+    /// if quoted)
+    pub offset: usize,
+    pub style: ScalarStyle,
+    /// The value after unquoting and unescaping
+    pub value: String,
+}
+
+/// Maps JSON-style paths (`rules[3].field`) to their position in the source.
+///
+/// Mapping entries point at their key, sequence items at the item itself.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    spans: BTreeMap<String, Span>,
+    scalars: BTreeMap<String, ScalarSpan>,
+}
+
+impl SourceMap {
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        let mut builder = SpanBuilder::default();
+        Parser::new_from_str(content).load(&mut builder, false)?;
+        Ok(SourceMap {
+            spans: builder.spans,
+            scalars: builder.scalars,
+        })
+    }
+
+    /// The scalar value at `path`, if the node there is a scalar without an
+    /// anchor or tag.
+    pub fn scalar(&self, path: &str) -> Option<&ScalarSpan> {
+        self.scalars.get(path)
+    }
+
+    /// Position of `path`, falling back to the closest ancestor that exists.
+    ///
+    /// A missing `rules[2].name` resolves to the `rules[2]` item, a missing
+    /// top-level field to the start of the document.
+    pub fn locate(&self, path: &str) -> Option<Span> {
+        let mut path = path;
+        loop {
+            if let Some(span) = self.spans.get(path) {
+                return Some(*span);
+            }
+            if path.is_empty() {
+                return None;
+            }
+            path = parent_path(path);
+        }
+    }
+}
+
+/// Append a key to a path (`rules[3]` + `field` -> `rules[3].field`).
+pub fn join_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+/// Strip the last segment of a path (`rules[3].field` -> `rules[3]` -> `rules`).
+pub fn parent_path(path: &str) -> &str {
+    match path.rfind(['.', '[']) {
+        Some(i) => &path[..i],
+        None => "",
+    }
+}
+
+enum Frame {
+    Mapping { path: String, key: Option<String> },
+    Sequence { path: String, index: usize },
+}
+
+#[derive(Default)]
+struct SpanBuilder {
+    stack: Vec<Frame>,
+    spans: BTreeMap<String, Span>,
+    scalars: BTreeMap<String, ScalarSpan>,
+}
+
+impl SpanBuilder {
+    /// Path of the node about to be read, or `None` if it is a mapping key.
+    fn value_path(&self) -> Option<String> {
+        match self.stack.last() {
+            None => Some(String::new()),
+            Some(Frame::Mapping { key: None, .. }) => None,
+            Some(Frame::Mapping {
+                path,
+                key: Some(key),
+            }) => Some(join_path(path, key)),
+            Some(Frame::Sequence { path, index }) => Some(format!("{}[{}]", path, index)),
+        }
+    }
+
+    /// Advance the enclosing container past a completed value.
+    fn finish_value(&mut self) {
+        match self.stack.last_mut() {
+            Some(Frame::Mapping { key, .. }) => *key = None,
+            Some(Frame::Sequence { index, .. }) => *index += 1,
+            None => {}
+        }
+    }
+
+    fn record(&mut self, path: &str, mark: Marker) {
+        self.spans.entry(path.to_string()).or_insert(Span {
+            line: mark.line(),
+            column: mark.col() + 1,
+        });
+    }
+}
+
+impl MarkedEventReceiver for SpanBuilder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        let path = match &event {
+            Event::Scalar(..)
+            | Event::Alias(_)
+            | Event::MappingStart(..)
+            | Event::SequenceStart(..) => {
+                match self.value_path() {
+                    Some(path) => path,
+                    None => {
+                        // Mapping key: remember it and point its path at the key itself
+                        let key = match &event {
+                            Event::Scalar(key, ..) => key.clone(),
+                            _ => String::from("?"),
+                        };
+                        let Some(Frame::Mapping { path, key: slot }) = self.stack.last_mut() else {
+                            return;
+                        };
+                        let container = path.clone();
+                        *slot = Some(key.clone());
+
+                        // Block mappings are reported at their first key
+                        self.record(&container, mark);
+                        self.record(&join_path(&container, &key), mark);
+                        return;
+                    }
+                }
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                if let Some(Frame::Mapping { path, .. } | Frame::Sequence { path, .. }) =
+                    self.stack.pop()
+                {
+                    // Empty containers never saw a key or item
+                    self.record(&path, mark);
+                }
+                self.finish_value();
+                return;
+            }
+            _ => return,
+        };
+
+        match event {
+            Event::MappingStart(..) => self.stack.push(Frame::Mapping { path, key: None }),
+            Event::SequenceStart(..) => {
+                self.record(&path, mark);
+                self.stack.push(Frame::Sequence { path, index: 0 })
+            }
+            Event::Scalar(value, style, 0, None) => {
+                self.record(&path, mark);
+                let style = match style {
+                    TScalarStyle::Plain => ScalarStyle::Plain,
+                    TScalarStyle::SingleQuoted => ScalarStyle::SingleQuoted,
+                    TScalarStyle::DoubleQuoted => ScalarStyle::DoubleQuoted,
+                    _ => ScalarStyle::Block,
+                };
+                self.scalars.insert(
+                    path,
+                    ScalarSpan {
+                        offset: mark.index(),
+                        style,
+                        value,
+                    },
+                );
+                self.finish_value()
+            }
+            _ => {
+                self.record(&path, mark);
+                self.finish_value()
+            }
+        }
+    }
+}
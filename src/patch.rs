@@ -0,0 +1,447 @@
+//! JSON Patch (RFC 6902) between specs, and applying patches to spec YAML.
+//!
+//! Patches address the spec as JSON (`/rules/0/weight`). A patch that only
+//! replaces scalar values, adds keys to block mappings and adds or removes
+//! items of block sequences is applied as in-place text edits, so comments
+//! and formatting survive. Any other patch rewrites the document from the
+//! patched value, keeping the original key order but losing comments.
+
+use anyhow::{anyhow, Result};
+use json_patch::jsonptr::Pointer;
+use json_patch::PatchOperation;
+use serde_json::Value;
+
+use crate::parser::{self, join_path, ScalarSpan, ScalarStyle, SourceMap};
+
+pub use json_patch::Patch;
+
+/// A spec after a patch was applied.
+#[derive(Debug, Clone)]
+pub struct Patched {
+    pub content: String,
+    /// Whether the original comments and formatting were kept
+    pub preserved: bool,
+}
+
+/// Patch that turns spec `old` into `new`.
+pub fn diff(old: &str, new: &str) -> Result<Patch> {
+    Ok(json_patch::diff(
+        &parser::parse_value(old)?,
+        &parser::parse_value(new)?,
+    ))
+}
+
+/// Apply `patch` to the spec YAML in `content`. Fails, leaving nothing
+/// half-applied, if any operation (including a `test`) fails.
+pub fn apply(content: &str, patch: &Patch) -> Result<Patched> {
+    apply_with_layouts(content, patch, &[])
+}
+
+/// Like [`apply`], but when the document has to be rewritten, keys that
+/// `content` does not order (new rules, sources, ...) follow their order in
+/// the `layouts` documents.
+pub fn apply_with_layouts(content: &str, patch: &Patch, layouts: &[&str]) -> Result<Patched> {
+    let original = parser::parse_value(content)?;
+    let mut document = original.clone();
+    json_patch::patch(&mut document, &patch.0).map_err(|e| anyhow!("Cannot apply patch: {}", e))?;
+
+    // Keep the edited text only if it reads back as exactly the patched value
+    if let Some(edited) = edit_in_place(content, patch) {
+        if parser::parse_value(&edited).ok().as_ref() == Some(&document) {
+            return Ok(Patched {
+                content: edited,
+                preserved: true,
+            });
+        }
+    }
+
+    let layouts = std::iter::once(content)
+        .chain(layouts.iter().copied())
+        .map(serde_yaml::from_str)
+        .collect::<Result<Vec<serde_yaml::Value>, _>>()?;
+    let layouts: Vec<&serde_yaml::Value> = layouts.iter().collect();
+    Ok(Patched {
+        content: serde_yaml::to_string(&ordered_like(&document, &layouts))?,
+        preserved: false,
+    })
+}
+
+/// Apply each operation as a text edit where the node stands, or `None` if
+/// the patch does anything else.
+fn edit_in_place(content: &str, patch: &Patch) -> Option<String> {
+    let mut text = content.to_string();
+    for operation in &patch.0 {
+        // Each edit moves what follows it, so re-read the text every time
+        let document = parser::parse_value(&text).ok()?;
+        let source_map = SourceMap::from_yaml(&text).ok()?;
+        text = match operation {
+            PatchOperation::Test(_) => continue,
+            PatchOperation::Replace(op) => {
+                replace_scalar(&text, &document, &source_map, &op.path, &op.value)?
+            }
+            PatchOperation::Add(op) => add(&text, &document, &source_map, &op.path, &op.value)?,
+            PatchOperation::Remove(op) => remove_item(&text, &document, &source_map, &op.path)?,
+            _ => return None,
+        };
+    }
+    Some(text)
+}
+
+/// Rewrite the scalar at `pointer` with `value`, keeping its quoting.
+fn replace_scalar(
+    text: &str,
+    document: &Value,
+    source_map: &SourceMap,
+    pointer: &Pointer,
+    value: &Value,
+) -> Option<String> {
+    let old = document.pointer(pointer.as_str())?;
+    if old.is_object() || old.is_array() || value.is_object() || value.is_array() {
+        return None;
+    }
+
+    let scalar = source_map.scalar(&to_path(pointer, document)?)?;
+    let start = text.char_indices().nth(scalar.offset)?.0;
+    let end = start + scalar_len(&text[start..], scalar)?;
+    let mut edited = text.to_string();
+    edited.replace_range(start..end, &render_scalar(value, scalar.style)?);
+    Some(edited)
+}
+
+/// Apply an `add`: a new item of a block sequence, a new key of a block
+/// mapping, or a new value for a key that is already there.
+fn add(
+    text: &str,
+    document: &Value,
+    source_map: &SourceMap,
+    pointer: &Pointer,
+    value: &Value,
+) -> Option<String> {
+    let (parent, token) = pointer.split_back()?;
+    match document.pointer(parent.as_str())? {
+        Value::Array(_) => add_item(text, document, source_map, pointer, value),
+        Value::Object(map) if map.contains_key(token.decoded().as_ref()) => {
+            replace_scalar(text, document, source_map, pointer, value)
+        }
+        Value::Object(_) => add_key(text, document, source_map, pointer, value),
+        _ => None,
+    }
+}
+
+/// Insert `key: value` after the last entry of a block mapping, indented
+/// like its other keys.
+fn add_key(
+    text: &str,
+    document: &Value,
+    source_map: &SourceMap,
+    pointer: &Pointer,
+    value: &Value,
+) -> Option<String> {
+    let (parent, token) = pointer.split_back()?;
+    let map = document.pointer(parent.as_str())?.as_object()?;
+    let path = to_path(parent, document)?;
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+
+    // Every key must start its line, after nothing but a sequence dash
+    let mut indent = None;
+    let mut at = 0;
+    for key in map.keys() {
+        let span = source_map.locate(&join_path(&path, key))?;
+        let line = lines.get(span.line.checked_sub(1)?)?;
+        let before: String = line.chars().take(span.column - 1).collect();
+        if !before.trim_start_matches([' ', '-']).is_empty()
+            || indent.is_some_and(|indent| indent != before.len())
+        {
+            return None;
+        }
+        indent = Some(before.len());
+        at = at.max(entry_end(&lines, span.line, before.len()));
+    }
+    let indent = " ".repeat(indent?);
+
+    let mut entry = serde_yaml::Mapping::new();
+    entry.insert(
+        serde_yaml::Value::String(token.decoded().into_owned()),
+        ordered_like(value, &[]),
+    );
+    let rendered: String = serde_yaml::to_string(&entry)
+        .ok()?
+        .lines()
+        .map(|line| format!("{}{}\n", indent, line))
+        .collect();
+
+    let mut edited: String = lines[..at].concat();
+    if !edited.ends_with('\n') {
+        edited.push('\n');
+    }
+    edited.push_str(&rendered);
+    edited.push_str(&lines[at..].concat());
+    Some(edited)
+}
+
+/// Line after the last non-blank line of the mapping entry whose key is on
+/// line `next` (0-based: the line after the key) at column `indent`.
+fn entry_end(lines: &[&str], next: usize, indent: usize) -> usize {
+    let mut end = next;
+    let mut last = next;
+    while let Some(line) = lines.get(end) {
+        let content = line.trim_start();
+        if !content.is_empty() {
+            // Block sequences may sit at their key's own indentation
+            let depth = line.len() - content.len();
+            if depth < indent || (depth == indent && !content.starts_with('-')) {
+                break;
+            }
+            last = end + 1;
+        }
+        end += 1;
+    }
+    last
+}
+
+/// Insert `value` as a new item of a block sequence, indented and with its
+/// keys ordered like the sequence's first item.
+fn add_item(
+    text: &str,
+    document: &Value,
+    source_map: &SourceMap,
+    pointer: &Pointer,
+    value: &Value,
+) -> Option<String> {
+    let (parent, token) = pointer.split_back()?;
+    let items = document.pointer(parent.as_str())?.as_array()?;
+    let index = if token.is_next() {
+        items.len()
+    } else {
+        token.decoded().parse().ok().filter(|i| *i <= items.len())?
+    };
+
+    // An existing item shows where the dashes go
+    let path = to_path(parent, document)?;
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let first = block_item(&lines, source_map, &format!("{}[0]", path))?;
+    let at = if index < items.len() {
+        block_item(&lines, source_map, &format!("{}[{}]", path, index))?.start
+    } else {
+        block_item(
+            &lines,
+            source_map,
+            &format!("{}[{}]", path, items.len() - 1),
+        )?
+        .end
+    };
+
+    let layout: serde_yaml::Value = serde_yaml::from_str(text).ok()?;
+    let sibling = layout_at(&layout, parent)?.as_sequence()?.first()?;
+    let item = serde_yaml::Value::Sequence(vec![ordered_like(value, &[sibling])]);
+    let rendered: String = serde_yaml::to_string(&item)
+        .ok()?
+        .lines()
+        .map(|line| format!("{}{}\n", first.indent, line))
+        .collect();
+
+    let mut edited: String = lines[..at].concat();
+    if !edited.is_empty() && !edited.ends_with('\n') {
+        edited.push('\n');
+    }
+    edited.push_str(&rendered);
+    edited.push_str(&lines[at..].concat());
+    Some(edited)
+}
+
+/// Delete the lines of a block sequence item.
+fn remove_item(
+    text: &str,
+    document: &Value,
+    source_map: &SourceMap,
+    pointer: &Pointer,
+) -> Option<String> {
+    let (parent, _) = pointer.split_back()?;
+    document.pointer(parent.as_str())?.as_array()?;
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let item = block_item(&lines, source_map, &to_path(pointer, document)?)?;
+    Some([&lines[..item.start], &lines[item.end..]].concat().concat())
+}
+
+/// The lines a block sequence item spans.
+struct BlockItem<'a> {
+    /// Whitespace before the item's `-`
+    indent: &'a str,
+    /// First line (the one with the `-`)
+    start: usize,
+    /// Line after the item's last non-blank line
+    end: usize,
+}
+
+/// Where the item at `path` sits, if it starts on a `- ` line of its own.
+fn block_item<'a>(lines: &[&'a str], source_map: &SourceMap, path: &str) -> Option<BlockItem<'a>> {
+    let span = source_map.locate(path)?;
+    let start = span.line.checked_sub(1)?;
+    let line = lines.get(start)?;
+    let before: String = line.chars().take(span.column - 1).collect();
+    let indent = &line[..line.len() - line.trim_start().len()];
+    if before.trim() != "-" {
+        return None;
+    }
+
+    // The item runs until a line indented no deeper than its dash
+    let mut end = start + 1;
+    let mut last = start + 1;
+    while let Some(line) = lines.get(end) {
+        let content = line.trim();
+        if !content.is_empty() {
+            if line.len() - line.trim_start().len() <= indent.len() {
+                break;
+            }
+            last = end + 1;
+        }
+        end += 1;
+    }
+    Some(BlockItem {
+        indent,
+        start,
+        end: last,
+    })
+}
+
+/// The node of a YAML document at a JSON pointer.
+fn layout_at<'a>(
+    layout: &'a serde_yaml::Value,
+    pointer: &Pointer,
+) -> Option<&'a serde_yaml::Value> {
+    let mut node = layout;
+    for token in pointer.tokens() {
+        let key = token.decoded();
+        node = match node {
+            serde_yaml::Value::Sequence(items) => items.get(key.parse::<usize>().ok()?)?,
+            _ => node.get(key.as_ref())?,
+        };
+    }
+    Some(node)
+}
+
+/// `/rules/0/weight` -> `rules[0].weight`, the path style of [`SourceMap`].
+fn to_path(pointer: &Pointer, document: &Value) -> Option<String> {
+    let mut path = String::new();
+    let mut node = document;
+    for token in pointer.tokens() {
+        let key = token.decoded();
+        match node {
+            Value::Array(items) => {
+                let index: usize = key.parse().ok()?;
+                node = items.get(index)?;
+                path = format!("{}[{}]", path, index);
+            }
+            Value::Object(map) => {
+                node = map.get(key.as_ref())?;
+                path = join_path(&path, &key);
+            }
+            _ => return None,
+        }
+    }
+    Some(path)
+}
+
+/// Length in bytes of the scalar at the start of `text`, if it sits on one
+/// line.
+fn scalar_len(text: &str, scalar: &ScalarSpan) -> Option<usize> {
+    match scalar.style {
+        // A one-line plain scalar is written exactly as its value
+        ScalarStyle::Plain => text
+            .starts_with(&scalar.value)
+            .then_some(scalar.value.len()),
+        ScalarStyle::SingleQuoted => {
+            let mut chars = text.char_indices().skip(1).peekable();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\n' => return None,
+                    '\'' if chars.peek().map(|(_, c)| *c) == Some('\'') => {
+                        chars.next();
+                    }
+                    '\'' => return Some(i + 1),
+                    _ => {}
+                }
+            }
+            None
+        }
+        ScalarStyle::DoubleQuoted => {
+            let mut chars = text.char_indices().skip(1);
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\n' => return None,
+                    '\\' => {
+                        chars.next();
+                    }
+                    '"' => return Some(i + 1),
+                    _ => {}
+                }
+            }
+            None
+        }
+        ScalarStyle::Block => None,
+    }
+}
+
+/// `value` written as a one-line YAML scalar, quoted the way the old value
+/// was where that still makes sense.
+fn render_scalar(value: &Value, style: ScalarStyle) -> Option<String> {
+    let text = match (value, style) {
+        (Value::String(s), ScalarStyle::DoubleQuoted) => serde_json::to_string(s).ok()?,
+        (Value::String(s), ScalarStyle::SingleQuoted) => format!("'{}'", s.replace('\'', "''")),
+        _ => serde_yaml::to_string(value).ok()?.trim_end().to_string(),
+    };
+    (!text.contains('\n')).then_some(text)
+}
+
+/// Convert the patched document to YAML, ordering mapping keys as in the
+/// first of `layouts` that has them. List items are matched to layout items
+/// by `name` where they have one, by position otherwise. Keys no layout
+/// knows come last.
+fn ordered_like(value: &Value, layouts: &[&serde_yaml::Value]) -> serde_yaml::Value {
+    match value {
+        Value::Object(map) => {
+            let mut mapping = serde_yaml::Mapping::new();
+            let known = layouts
+                .iter()
+                .filter_map(|layout| layout.as_mapping())
+                .flat_map(|layout| layout.keys().filter_map(serde_yaml::Value::as_str));
+            let keys = known.chain(map.keys().map(String::as_str));
+            for key in keys {
+                let yaml_key = serde_yaml::Value::String(key.to_string());
+                let Some(value) = map.get(key) else {
+                    continue;
+                };
+                if mapping.contains_key(&yaml_key) {
+                    continue;
+                }
+                let children: Vec<&serde_yaml::Value> = layouts
+                    .iter()
+                    .filter_map(|layout| layout.get(key))
+                    .collect();
+                mapping.insert(yaml_key, ordered_like(value, &children));
+            }
+            serde_yaml::Value::Mapping(mapping)
+        }
+        Value::Array(items) => serde_yaml::Value::Sequence(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    let name = item.get("name").and_then(Value::as_str);
+                    let children: Vec<&serde_yaml::Value> = layouts
+                        .iter()
+                        .filter_map(|layout| layout.as_sequence())
+                        .filter_map(|layout| match name {
+                            Some(name) => layout.iter().find(|l| {
+                                l.get("name").and_then(serde_yaml::Value::as_str) == Some(name)
+                            }),
+                            None => layout.get(i),
+                        })
+                        .collect();
+                    ordered_like(item, &children)
+                })
+                .collect(),
+        ),
+        _ => serde_yaml::to_value(value).unwrap_or(serde_yaml::Value::Null),
+    }
+}
@@ -0,0 +1,352 @@
+//! Registry of the rule types a spec can use.
+//!
+//! Each type declares the parameters it takes (beyond `name`, `field`,
+//! `weight` and `threshold`), the values they accept and the kind of
+//! attribute it compares. [`check`] reports rules that break these
+//! declarations. A `threshold` or parameter the type ignores, and an
+//! optional parameter left to its default, are left to [`crate::lint`]: the
+//! rule still runs as written, and specs from before the registry use both.
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::diagnostic::{did_you_mean, Code, Diagnostic};
+use crate::spec::Spec;
+
+/// Keys every rule or blocking section may carry besides its parameters.
+const COMMON_KEYS: &[&str] = &["description"];
+
+/// The kind of value a rule type compares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    /// Anything that can be compared for equality
+    Any,
+    Text,
+    Number,
+    Date,
+}
+
+impl ValueKind {
+    /// Whether one attribute can be compared as both `self` and `other`.
+    pub fn compatible(&self, other: ValueKind) -> bool {
+        *self == ValueKind::Any || other == ValueKind::Any || *self == other
+    }
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValueKind::Any => "any value",
+            ValueKind::Text => "text",
+            ValueKind::Number => "a number",
+            ValueKind::Date => "a date",
+        })
+    }
+}
+
+/// Values a parameter accepts.
+#[derive(Debug, Clone, Copy)]
+pub enum ParamKind {
+    Number { min: f64, max: Option<f64> },
+    Integer { min: i64 },
+    OneOf(&'static [&'static str]),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Param {
+    pub name: &'static str,
+    pub kind: ParamKind,
+    pub required: bool,
+    /// Value used when an optional parameter is not set
+    pub default: Option<&'static str>,
+}
+
+impl Param {
+    /// Why `value` is not accepted, if it is not.
+    pub fn problem(&self, value: &Value) -> Option<String> {
+        match self.kind {
+            ParamKind::Number { min, max } => match value.as_f64() {
+                None => Some("must be a number".to_string()),
+                Some(n) if n < min => Some(format!("must be at least {}", min)),
+                Some(n) if max.is_some_and(|max| n > max) => {
+                    Some(format!("must be at most {}", max.unwrap_or_default()))
+                }
+                Some(_) => None,
+            },
+            ParamKind::Integer { min } => match value.as_i64() {
+                None => Some("must be a whole number".to_string()),
+                Some(n) if n < min => Some(format!("must be at least {}", min)),
+                Some(_) => None,
+            },
+            ParamKind::OneOf(choices) => match value.as_str() {
+                Some(s) if choices.contains(&s) => None,
+                _ => Some(format!("must be one of: {}", choices.join(", "))),
+            },
+        }
+    }
+}
+
+/// What is wrong with one declared parameter.
+#[derive(Debug, Clone)]
+pub enum ParamProblem {
+    /// Required but not set
+    Missing(&'static Param),
+    /// Set to a value the parameter does not accept, and why
+    Invalid(&'static Param, String),
+}
+
+/// Check `params` against the parameters a rule type or blocking strategy
+/// declares.
+pub fn check_params(
+    params: &BTreeMap<String, Value>,
+    declared: &'static [Param],
+) -> Vec<ParamProblem> {
+    declared
+        .iter()
+        .filter_map(|param| match params.get(param.name) {
+            None if param.required => Some(ParamProblem::Missing(param)),
+            None => None,
+            Some(value) => param
+                .problem(value)
+                .map(|problem| ParamProblem::Invalid(param, problem)),
+        })
+        .collect()
+}
+
+/// Keys of `params` that none of the `declared` parameters take.
+pub fn unknown_params<'a>(params: &'a BTreeMap<String, Value>, declared: &[Param]) -> Vec<&'a str> {
+    params
+        .keys()
+        .map(String::as_str)
+        .filter(|key| !COMMON_KEYS.contains(key) && !declared.iter().any(|p| p.name == *key))
+        .collect()
+}
+
+/// Optional parameters `params` leaves unset, with the default used instead.
+pub fn implicit_params<'a>(
+    params: &'a BTreeMap<String, Value>,
+    declared: &'static [Param],
+) -> impl Iterator<Item = (&'static Param, &'static str)> + 'a {
+    declared.iter().filter_map(|param| {
+        param
+            .default
+            .filter(|_| !params.contains_key(param.name))
+            .map(|default| (param, default))
+    })
+}
+
+/// A known rule type.
+#[derive(Debug, Clone, Copy)]
+pub struct RuleType {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// What the compared attribute holds
+    pub values: ValueKind,
+    /// Whether `threshold` changes the outcome; types that only ever agree
+    /// or disagree ignore it
+    pub uses_threshold: bool,
+    pub params: &'static [Param],
+}
+
+const STRING_ALGORITHMS: &[&str] = &["jaro_winkler", "jaro", "levenshtein"];
+pub const PHONETIC_ALGORITHMS: &[&str] = &["soundex", "metaphone"];
+
+pub const RULE_TYPES: &[RuleType] = &[
+    RuleType {
+        name: "exact",
+        description: "Values are equal",
+        values: ValueKind::Any,
+        uses_threshold: false,
+        params: &[],
+    },
+    RuleType {
+        name: "fuzzy",
+        description: "String similarity with the chosen `algorithm`",
+        values: ValueKind::Text,
+        uses_threshold: true,
+        params: &[Param {
+            name: "algorithm",
+            kind: ParamKind::OneOf(STRING_ALGORITHMS),
+            required: false,
+            default: Some("jaro_winkler"),
+        }],
+    },
+    RuleType {
+        name: "similarity",
+        description: "String similarity with the `algorithm` it names",
+        values: ValueKind::Text,
+        uses_threshold: true,
+        params: &[Param {
+            name: "algorithm",
+            kind: ParamKind::OneOf(STRING_ALGORITHMS),
+            required: true,
+            default: None,
+        }],
+    },
+    RuleType {
+        name: "jaro_winkler",
+        description: "Jaro-Winkler similarity",
+        values: ValueKind::Text,
+        uses_threshold: true,
+        params: &[],
+    },
+    RuleType {
+        name: "jaro",
+        description: "Jaro similarity",
+        values: ValueKind::Text,
+        uses_threshold: true,
+        params: &[],
+    },
+    RuleType {
+        name: "levenshtein",
+        description: "Normalized Levenshtein similarity",
+        values: ValueKind::Text,
+        uses_threshold: true,
+        params: &[],
+    },
+    RuleType {
+        name: "phonetic",
+        description: "Values sound alike under the chosen `algorithm`",
+        values: ValueKind::Text,
+        uses_threshold: false,
+        params: &[Param {
+            name: "algorithm",
+            kind: ParamKind::OneOf(PHONETIC_ALGORITHMS),
+            required: false,
+            default: Some("soundex"),
+        }],
+    },
+    RuleType {
+        name: "soundex",
+        description: "Values have the same Soundex code",
+        values: ValueKind::Text,
+        uses_threshold: false,
+        params: &[],
+    },
+    RuleType {
+        name: "metaphone",
+        description: "Values have the same Metaphone code",
+        values: ValueKind::Text,
+        uses_threshold: false,
+        params: &[],
+    },
+    RuleType {
+        name: "numeric_range",
+        description: "Numbers differ by at most `tolerance`",
+        values: ValueKind::Number,
+        uses_threshold: false,
+        params: &[Param {
+            name: "tolerance",
+            kind: ParamKind::Number {
+                min: 0.0,
+                max: None,
+            },
+            required: true,
+            default: None,
+        }],
+    },
+    RuleType {
+        name: "date_proximity",
+        description: "Dates are at most `max_days` apart",
+        values: ValueKind::Date,
+        uses_threshold: false,
+        params: &[Param {
+            name: "max_days",
+            kind: ParamKind::Integer { min: 0 },
+            required: true,
+            default: None,
+        }],
+    },
+];
+
+/// The registered type called `name`.
+pub fn lookup(name: &str) -> Option<&'static RuleType> {
+    RULE_TYPES.iter().find(|rule_type| rule_type.name == name)
+}
+
+/// The registered type name closest to a misspelled `name`.
+pub fn suggest(name: &str) -> Option<&'static str> {
+    did_you_mean(name, RULE_TYPES.iter().map(|rule_type| rule_type.name))
+}
+
+/// Check every rule against its registered type.
+pub fn check(spec: &Spec) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+    // Attribute -> (kind, rule) of the first rule that compares it as
+    // something specific
+    let mut kinds: Vec<(&str, ValueKind, &str)> = Vec::new();
+
+    for (i, rule) in spec.rules.iter().enumerate() {
+        let Some(type_name) = rule.rule_type.as_deref() else {
+            continue;
+        };
+        let rule_name = rule.name.as_deref().unwrap_or("unknown");
+
+        let Some(rule_type) = lookup(type_name) else {
+            let mut diagnostic = Diagnostic::error(
+                Code::UnknownRuleType,
+                format!("rules[{}].type", i),
+                format!("Rule '{}' has unknown type '{}'", rule_name, type_name),
+            );
+            diagnostic = match suggest(type_name) {
+                Some(suggestion) => diagnostic.with_help(format!("Did you mean '{}'?", suggestion)),
+                None => diagnostic.with_help(format!(
+                    "Known types: {}",
+                    RULE_TYPES
+                        .iter()
+                        .map(|t| t.name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+            };
+            errors.push(diagnostic);
+            continue;
+        };
+
+        for problem in check_params(&rule.params, rule_type.params) {
+            errors.push(match problem {
+                ParamProblem::Missing(param) => Diagnostic::error(
+                    Code::InvalidRuleParameter,
+                    format!("rules[{}]", i),
+                    format!(
+                        "Rule '{}' ({}) is missing required parameter '{}'",
+                        rule_name, rule_type.name, param.name
+                    ),
+                )
+                .with_help(format!("Add '{}' to the rule", param.name)),
+                ParamProblem::Invalid(param, problem) => Diagnostic::error(
+                    Code::InvalidRuleParameter,
+                    format!("rules[{}].{}", i, param.name),
+                    format!(
+                        "Parameter '{}' of rule '{}' {}",
+                        param.name, rule_name, problem
+                    ),
+                ),
+            });
+        }
+
+        let Some(field) = rule.field.as_deref() else {
+            continue;
+        };
+        if rule_type.values == ValueKind::Any {
+            continue;
+        }
+        match kinds.iter().find(|(f, _, _)| *f == field) {
+            Some((_, kind, other)) if !kind.compatible(rule_type.values) => {
+                errors.push(Diagnostic::error(
+                    Code::IncompatibleRuleField,
+                    format!("rules[{}].field", i),
+                    format!(
+                        "Rule '{}' compares '{}' as {}, but rule '{}' compares it as {}",
+                        rule_name, field, rule_type.values, other, kind
+                    ),
+                ));
+            }
+            Some(_) => {}
+            None => kinds.push((field, rule_type.values, rule_name)),
+        }
+    }
+
+    errors
+}
@@ -0,0 +1,278 @@
+//! Embedded JSON Schemas for each supported `api_version`.
+//!
+//! The schemas in `schemas/` are the published contract for spec files; they
+//! are compiled into the binary so the validator can never drift from them.
+//! The schema for compiled plans (the IR) lives alongside them.
+
+use anyhow::{anyhow, Result};
+use jsonschema::error::ValidationErrorKind;
+use jsonschema::paths::{JSONPointer, PathChunk};
+use jsonschema::{Draft, JSONSchema, ValidationError};
+use serde_json::Value;
+use std::sync::OnceLock;
+
+use crate::diagnostic::{Code, Diagnostic};
+use crate::ir::IR_VERSION;
+use crate::parser::join_path;
+use crate::spec::SUPPORTED_API_VERSIONS;
+
+/// Version used when a document does not declare a supported one.
+pub const LATEST_API_VERSION: &str = "kanoniv/v2";
+
+/// Raw JSON Schema text for an `api_version`.
+pub fn schema_source(api_version: &str) -> Option<&'static str> {
+    match api_version {
+        "kanoniv/v1" => Some(include_str!("../schemas/kanoniv-v1.schema.json")),
+        "kanoniv/v2" => Some(include_str!("../schemas/kanoniv-v2.schema.json")),
+        _ => None,
+    }
+}
+
+/// Raw JSON Schema text for the current [`IR_VERSION`].
+pub fn ir_schema_source() -> &'static str {
+    include_str!("../schemas/kanoniv-ir-v1.schema.json")
+}
+
+/// Parsed JSON Schema for an `api_version`.
+pub fn schema_for(api_version: &str) -> Option<Value> {
+    schema_source(api_version)
+        .map(|s| serde_json::from_str(s).expect("embedded schema is valid JSON"))
+}
+
+fn compiled(api_version: &str) -> Result<&'static JSONSchema> {
+    static SCHEMAS: OnceLock<Vec<(&'static str, JSONSchema)>> = OnceLock::new();
+
+    let schemas = SCHEMAS.get_or_init(|| {
+        SUPPORTED_API_VERSIONS
+            .iter()
+            .filter_map(|version| {
+                let schema = schema_for(version)?;
+                let compiled = JSONSchema::options()
+                    .with_draft(Draft::Draft7)
+                    .compile(&schema)
+                    .ok()?;
+                Some((*version, compiled))
+            })
+            .collect()
+    });
+
+    schemas
+        .iter()
+        .find(|(version, _)| *version == api_version)
+        .map(|(_, schema)| schema)
+        .ok_or_else(|| anyhow!("No schema available for api_version '{}'", api_version))
+}
+
+/// Check a raw spec document against the schema for its `api_version`.
+///
+/// Documents with a missing or unsupported version are checked against
+/// [`LATEST_API_VERSION`]; an unsupported version is reported as well.
+pub fn validate_document(document: &Value) -> Result<Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+
+    let declared = document.get("api_version").and_then(|v| v.as_str());
+    let api_version = match declared {
+        Some(version) if schema_source(version).is_some() => version,
+        Some(version) if version.starts_with("kanoniv/v") => {
+            diagnostics.push(
+                Diagnostic::error(
+                    Code::UnsupportedApiVersion,
+                    "api_version",
+                    format!("Unsupported api_version: '{}'", version),
+                )
+                .with_help(format!(
+                    "Supported versions: {}",
+                    SUPPORTED_API_VERSIONS.join(", ")
+                )),
+            );
+            LATEST_API_VERSION
+        }
+        _ => LATEST_API_VERSION,
+    };
+
+    if let Err(errors) = compiled(api_version)?.validate(document) {
+        diagnostics.extend(errors.map(to_diagnostic));
+    }
+
+    Ok(diagnostics)
+}
+
+/// Check a compiled plan against the IR schema.
+///
+/// A plan written for another `ir_version` is reported without checking its
+/// layout, since this build cannot know what that layout is.
+pub fn validate_ir(plan: &Value) -> Result<Vec<Diagnostic>> {
+    static SCHEMA: OnceLock<Option<JSONSchema>> = OnceLock::new();
+
+    match plan.get("ir_version").and_then(|v| v.as_str()) {
+        Some(IR_VERSION) | None => {}
+        Some(version) => {
+            return Ok(vec![Diagnostic::error(
+                Code::UnsupportedIrVersion,
+                "ir_version",
+                format!("Unsupported ir_version: '{}'", version),
+            )
+            .with_help(format!(
+                "This build reads ir_version '{}'; recompile the spec",
+                IR_VERSION
+            ))]);
+        }
+    }
+
+    let schema = SCHEMA
+        .get_or_init(|| {
+            let schema = serde_json::from_str(ir_schema_source()).ok()?;
+            JSONSchema::options()
+                .with_draft(Draft::Draft7)
+                .compile(&schema)
+                .ok()
+        })
+        .as_ref()
+        .ok_or_else(|| anyhow!("The embedded IR schema failed to compile"))?;
+
+    Ok(match schema.validate(plan) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors.map(to_diagnostic).collect(),
+    })
+}
+
+/// Convert a JSON Pointer into the `rules[3].field` notation used by diagnostics.
+fn json_path(pointer: &JSONPointer) -> String {
+    let mut path = String::new();
+    for chunk in pointer.iter() {
+        match chunk {
+            PathChunk::Property(name) => path = join_path(&path, name),
+            PathChunk::Index(i) => path.push_str(&format!("[{}]", i)),
+            PathChunk::Keyword(_) => {}
+        }
+    }
+    path
+}
+
+fn to_diagnostic(error: ValidationError) -> Diagnostic {
+    let path = json_path(&error.instance_path);
+    let instance = error.instance.as_ref();
+
+    match &error.kind {
+        ValidationErrorKind::Required { property } => {
+            let property = property.as_str().unwrap_or_default();
+            let message = if path.is_empty() {
+                format!("Missing required field: {}", property)
+            } else {
+                format!("{}: missing required field '{}'", path, property)
+            };
+            Diagnostic::error(
+                Code::MissingRequiredField,
+                join_path(&path, property),
+                message,
+            )
+        }
+        ValidationErrorKind::AdditionalProperties { unexpected } => {
+            let location = if path.is_empty() {
+                "spec"
+            } else {
+                path.as_str()
+            };
+            let message = format!(
+                "Unknown field(s) in {}: {}",
+                location,
+                unexpected.join(", ")
+            );
+            let path = match unexpected.first() {
+                Some(first) => join_path(&path, first),
+                None => path,
+            };
+            Diagnostic::error(Code::UnknownProperty, path, message)
+        }
+        ValidationErrorKind::Type { kind } => Diagnostic::error(
+            Code::InvalidType,
+            path.clone(),
+            format!("{}: expected {}, found {}", path, type_name(kind), instance),
+        ),
+        ValidationErrorKind::Enum { options } => Diagnostic::error(
+            Code::InvalidEnumValue,
+            path.clone(),
+            format!("{}: {} is not an allowed value", path, instance),
+        )
+        .with_help(format!("Expected one of: {}", join_values(options))),
+        ValidationErrorKind::Minimum { .. }
+        | ValidationErrorKind::Maximum { .. }
+        | ValidationErrorKind::ExclusiveMinimum { .. }
+        | ValidationErrorKind::ExclusiveMaximum { .. } => range_diagnostic(path, instance),
+        ValidationErrorKind::MaxItems { limit } => {
+            let count = instance.as_array().map(|a| a.len()).unwrap_or_default();
+            let (code, what) = match path.as_str() {
+                "rules" => (Code::TooManyRules, "rules"),
+                "sources" => (Code::TooManySources, "sources"),
+                "blocking.keys" => (Code::TooManyBlockingKeys, "blocking keys"),
+                _ => (Code::SchemaViolation, "items"),
+            };
+            Diagnostic::error(
+                code,
+                path.clone(),
+                format!("Too many {}: {}. Maximum is {}.", what, count, limit),
+            )
+        }
+        ValidationErrorKind::Pattern { .. } if path == "api_version" => Diagnostic::error(
+            Code::InvalidApiVersion,
+            path,
+            format!(
+                "Invalid api_version format: {}. Expected 'kanoniv/v<N>'",
+                quoted(instance)
+            ),
+        ),
+        ValidationErrorKind::MinLength { .. } => Diagnostic::error(
+            Code::SchemaViolation,
+            path.clone(),
+            format!("{}: must not be empty", path),
+        ),
+        _ => Diagnostic::error(
+            Code::SchemaViolation,
+            path.clone(),
+            format!("{}: {}", path, error),
+        ),
+    }
+}
+
+fn range_diagnostic(path: String, instance: &Value) -> Diagnostic {
+    // Keep the wording of the historical per-field checks
+    let (parent, field) = match path.rsplit_once('.') {
+        Some((parent, field)) => (parent.to_string(), field.to_string()),
+        None => (String::new(), path.clone()),
+    };
+    let code = match field.as_str() {
+        "weight" => Code::WeightOutOfRange,
+        "threshold" | "match" | "review" | "reject" => Code::ThresholdOutOfRange,
+        _ => Code::ValueOutOfRange,
+    };
+    Diagnostic::error(
+        code,
+        path.clone(),
+        format!("{}: {} {} must be between 0 and 1", parent, field, instance),
+    )
+}
+
+fn type_name(kind: &jsonschema::error::TypeKind) -> String {
+    match kind {
+        jsonschema::error::TypeKind::Single(t) => t.to_string(),
+        jsonschema::error::TypeKind::Multiple(types) => types
+            .into_iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>()
+            .join(" or "),
+    }
+}
+
+fn join_values(options: &Value) -> String {
+    options
+        .as_array()
+        .map(|values| values.iter().map(quoted).collect::<Vec<_>>().join(", "))
+        .unwrap_or_else(|| options.to_string())
+}
+
+fn quoted(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("'{}'", s),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,505 @@
+//! Offline evaluation of a spec's rules against a pair of records.
+//!
+//! A rule passes when the similarity of the two field values reaches the
+//! rule's `threshold`; a passing rule contributes its `weight` to the score.
+//! Contributions are combined according to `decision.aggregation` and the
+//! result is placed in a `decision.thresholds` band.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+
+use crate::spec::{Rule, Spec, Thresholds};
+
+/// How rule contributions are combined into a pair score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    /// Sum of the weights of passing rules
+    WeightedSum,
+    /// Weighted sum divided by the total weight of all rules
+    WeightedAverage,
+    /// Largest weight among passing rules
+    Max,
+}
+
+impl Aggregation {
+    pub fn parse(name: &str) -> Option<Aggregation> {
+        match name {
+            "weighted_sum" => Some(Aggregation::WeightedSum),
+            "weighted_average" => Some(Aggregation::WeightedAverage),
+            "max" => Some(Aggregation::Max),
+            _ => None,
+        }
+    }
+
+    /// Aggregation declared by a spec, defaulting to `weighted_sum`.
+    pub fn of(spec: &Spec) -> Aggregation {
+        spec.decision
+            .as_ref()
+            .and_then(|d| d.aggregation.as_deref())
+            .and_then(Aggregation::parse)
+            .unwrap_or(Aggregation::WeightedSum)
+    }
+
+    /// Share of the score each rule contributes, given `(weight, passed)`
+    /// per rule. The score is the sum of the contributions.
+    pub fn contributions(&self, outcomes: &[(f64, bool)]) -> Vec<f64> {
+        let passed = |(w, p): &(f64, bool)| if *p { *w } else { 0.0 };
+        match self {
+            Aggregation::WeightedSum => outcomes.iter().map(passed).collect(),
+            Aggregation::WeightedAverage => {
+                let total: f64 = outcomes.iter().map(|(w, _)| *w).sum();
+                outcomes
+                    .iter()
+                    .map(|o| if total > 0.0 { passed(o) / total } else { 0.0 })
+                    .collect()
+            }
+            Aggregation::Max => {
+                // Only the strongest passing rule counts
+                let best = outcomes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, p))| *p)
+                    .max_by(|(_, a), (_, b)| a.0.total_cmp(&b.0))
+                    .map(|(i, _)| i);
+                outcomes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, o)| if Some(i) == best { o.0 } else { 0.0 })
+                    .collect()
+            }
+        }
+    }
+
+    /// Score range for rules with `weights`. Each rule passes or fails
+    /// independently, so the extremes come from the rules pulling one way.
+    pub fn range(&self, weights: &[f64]) -> ScoreRange {
+        let positive = weights.iter().filter(|w| **w > 0.0).fold(0.0, |a, w| a + w);
+        let negative = weights.iter().filter(|w| **w < 0.0).fold(0.0, |a, w| a + w);
+        match self {
+            Aggregation::WeightedSum => ScoreRange {
+                min: negative,
+                max: positive,
+            },
+            Aggregation::WeightedAverage => {
+                let total = positive + negative;
+                if total > 0.0 {
+                    ScoreRange {
+                        min: negative / total,
+                        max: positive / total,
+                    }
+                } else {
+                    ScoreRange { min: 0.0, max: 0.0 }
+                }
+            }
+            // No passing rule scores 0, otherwise the best passing weight
+            Aggregation::Max => ScoreRange {
+                min: weights.iter().copied().fold(0.0, f64::min),
+                max: weights.iter().copied().fold(0.0, f64::max),
+            },
+        }
+    }
+}
+
+/// Lowest and highest score a pair can get, over every combination of
+/// rules passing and failing.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ScoreRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Scores are sums of floats: `0.6 + 0.3 + 0.1` must still reach `1.0`.
+const SCORE_EPSILON: f64 = 1e-9;
+
+impl ScoreRange {
+    /// Whether some pair can score `threshold` or more.
+    pub fn reaches(&self, threshold: f64) -> bool {
+        threshold <= self.max + SCORE_EPSILON
+    }
+
+    /// Whether every pair scores `threshold` or more.
+    pub fn always_reaches(&self, threshold: f64) -> bool {
+        threshold <= self.min + SCORE_EPSILON
+    }
+}
+
+/// `score` rounded for display, so float noise does not show.
+pub fn round_score(score: f64) -> f64 {
+    (score * 1e6).round() / 1e6
+}
+
+/// Score range of a spec under its rule weights and aggregation.
+pub fn score_range(spec: &Spec) -> ScoreRange {
+    let weights: Vec<f64> = spec.rules.iter().map(Rule::weight_or_default).collect();
+    Aggregation::of(spec).range(&weights)
+}
+
+impl fmt::Display for Aggregation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Aggregation::WeightedSum => "weighted_sum",
+            Aggregation::WeightedAverage => "weighted_average",
+            Aggregation::Max => "max",
+        })
+    }
+}
+
+/// The decision band a score falls into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Band {
+    Match,
+    Review,
+    Reject,
+}
+
+impl Band {
+    /// Band for `score`, with missing thresholds filled in by
+    /// [`Thresholds::match_or_default`] and friends.
+    pub fn for_score(score: f64, thresholds: Option<&Thresholds>) -> Band {
+        let thresholds = thresholds.copied().unwrap_or_default();
+        if score >= thresholds.match_or_default() {
+            Band::Match
+        } else if score >= thresholds.review_or_default() {
+            Band::Review
+        } else {
+            Band::Reject
+        }
+    }
+}
+
+impl fmt::Display for Band {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Band::Match => "match",
+            Band::Review => "review",
+            Band::Reject => "reject",
+        })
+    }
+}
+
+/// How one rule scored a pair.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleOutcome {
+    pub rule: String,
+    #[serde(rename = "type")]
+    pub rule_type: String,
+    pub field: Option<String>,
+    pub left: Option<Value>,
+    pub right: Option<Value>,
+    /// Similarity in [0, 1]; `None` when the rule could not be evaluated
+    pub similarity: Option<f64>,
+    pub threshold: f64,
+    pub passed: bool,
+    pub weight: f64,
+    /// Share of the pair score, after aggregation
+    pub contribution: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+    pub rules: Vec<RuleOutcome>,
+    pub aggregation: Aggregation,
+    pub score: f64,
+    pub band: Band,
+    pub thresholds: Option<Thresholds>,
+}
+
+/// Score a pair of records (keyed by attribute name) against every rule.
+pub fn explain(spec: &Spec, left: &Map<String, Value>, right: &Map<String, Value>) -> Explanation {
+    let aggregation = Aggregation::of(spec);
+
+    let mut rules: Vec<RuleOutcome> = spec
+        .rules
+        .iter()
+        .map(|rule| evaluate(rule, left, right))
+        .collect();
+
+    let outcomes: Vec<(f64, bool)> = rules.iter().map(|r| (r.weight, r.passed)).collect();
+    let contributions = aggregation.contributions(&outcomes);
+    for (outcome, contribution) in rules.iter_mut().zip(&contributions) {
+        outcome.contribution = *contribution;
+    }
+    let score: f64 = contributions.iter().sum();
+
+    Explanation {
+        rules,
+        aggregation,
+        score,
+        band: Band::for_score(score, spec.thresholds()),
+        thresholds: spec.thresholds().copied(),
+    }
+}
+
+fn evaluate(rule: &Rule, left: &Map<String, Value>, right: &Map<String, Value>) -> RuleOutcome {
+    let rule_type = rule.rule_type.clone().unwrap_or_default();
+    let weight = rule.weight_or_default();
+    let threshold = rule.threshold_or_default();
+    let left_value = rule
+        .field
+        .as_ref()
+        .and_then(|f| left.get(f))
+        .filter(|v| !v.is_null());
+    let right_value = rule
+        .field
+        .as_ref()
+        .and_then(|f| right.get(f))
+        .filter(|v| !v.is_null());
+
+    let mut outcome = RuleOutcome {
+        rule: rule.name.clone().unwrap_or_default(),
+        rule_type: rule_type.clone(),
+        field: rule.field.clone(),
+        left: left_value.cloned(),
+        right: right_value.cloned(),
+        similarity: None,
+        threshold,
+        passed: false,
+        weight,
+        contribution: 0.0,
+        note: None,
+    };
+
+    let (Some(a), Some(b)) = (left_value, right_value) else {
+        outcome.note = Some("missing value".to_string());
+        return outcome;
+    };
+
+    let algorithm = rule.algorithm().unwrap_or_default();
+    let limit = |name: &str| rule.params.get(name).and_then(Value::as_f64);
+    let result = match algorithm {
+        "numeric_range" => within(limit("tolerance"), number(a), number(b))
+            .ok_or_else(|| "values are not numbers".to_string()),
+        "date_proximity" => within(limit("max_days"), days(a), days(b))
+            .ok_or_else(|| "values are not dates (YYYY-MM-DD)".to_string()),
+        _ => similarity(algorithm, &as_text(a), &as_text(b))
+            .ok_or_else(|| format!("rule type '{}' cannot be evaluated offline", algorithm)),
+    };
+    match result {
+        Ok(similarity) => {
+            outcome.similarity = Some(similarity);
+            outcome.passed = similarity >= threshold;
+        }
+        Err(note) => outcome.note = Some(note),
+    }
+    outcome
+}
+
+/// 1 when `a` and `b` are at most `limit` apart, 0 when they are further.
+fn within(limit: Option<f64>, a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    Some(f64::from(u8::from((a? - b?).abs() <= limit?)))
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(s) => s.trim().parse().ok(),
+        other => other.as_f64(),
+    }
+}
+
+/// Days since 1970-01-01 of a `YYYY-MM-DD` date, or the date part of an
+/// ISO 8601 timestamp.
+fn days(value: &Value) -> Option<f64> {
+    let text = value.as_str()?.trim();
+    let date = text.get(..10)?;
+    if !matches!(text.as_bytes().get(10), None | Some(b'T' | b' ')) {
+        return None;
+    }
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days from civil, counting years from March so leap days come last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some((era * 146_097 + day_of_era - 719_468) as f64)
+}
+
+/// Similarity of two values in [0, 1] for a comparison algorithm.
+pub fn similarity(algorithm: &str, a: &str, b: &str) -> Option<f64> {
+    let (a, b) = (a.trim(), b.trim());
+    let score = match algorithm {
+        "exact" => f64::from(u8::from(a == b)),
+        "fuzzy" | "jaro_winkler" => strsim::jaro_winkler(&a.to_lowercase(), &b.to_lowercase()),
+        "jaro" => strsim::jaro(&a.to_lowercase(), &b.to_lowercase()),
+        "levenshtein" => strsim::normalized_levenshtein(&a.to_lowercase(), &b.to_lowercase()),
+        "phonetic" | "soundex" => f64::from(u8::from(soundex(a) == soundex(b) && !a.is_empty())),
+        "metaphone" => f64::from(u8::from(metaphone(a) == metaphone(b) && !a.is_empty())),
+        _ => return None,
+    };
+    Some(score)
+}
+
+/// American Soundex code (`Robert` -> `R163`).
+pub fn soundex(s: &str) -> String {
+    fn digit(c: char) -> Option<char> {
+        match c {
+            'b' | 'f' | 'p' | 'v' => Some('1'),
+            'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+            'd' | 't' => Some('3'),
+            'l' => Some('4'),
+            'm' | 'n' => Some('5'),
+            'r' => Some('6'),
+            _ => None,
+        }
+    }
+
+    let letters: Vec<char> = s
+        .chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    let Some(&first) = letters.first() else {
+        return String::new();
+    };
+
+    let mut code = first.to_ascii_uppercase().to_string();
+    let mut last = digit(first);
+    for &c in &letters[1..] {
+        let d = digit(c);
+        if let Some(digit) = d.filter(|_| d != last) {
+            code.push(digit);
+        }
+        // 'h' and 'w' do not separate letters with the same code
+        if c != 'h' && c != 'w' {
+            last = d;
+        }
+        if code.len() == 4 {
+            break;
+        }
+    }
+    format!("{:0<4}", code)
+}
+
+/// Metaphone code (`Knight` -> `NT`, `Smith` -> `SM0`), after Lawrence
+/// Philips' original rules; `0` stands for "th".
+pub fn metaphone(s: &str) -> String {
+    let mut word: Vec<char> = s
+        .chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    match word.as_slice() {
+        ['A', 'E', ..] | ['G' | 'K' | 'P', 'N', ..] | ['W', 'R', ..] => {
+            word.remove(0);
+        }
+        ['W', 'H', ..] => {
+            word.remove(1);
+        }
+        ['X', ..] => word[0] = 'S',
+        _ => {}
+    }
+
+    let is_vowel = |c: Option<char>| matches!(c, Some('A' | 'E' | 'I' | 'O' | 'U'));
+    let at = |i: usize| word.get(i).copied();
+    let mut code = String::new();
+    for (i, &c) in word.iter().enumerate() {
+        let prev = i.checked_sub(1).and_then(at);
+        let next = at(i + 1);
+        let after = at(i + 2);
+        if prev == Some(c) && c != 'C' {
+            continue;
+        }
+        match c {
+            'A' | 'E' | 'I' | 'O' | 'U' => {
+                if i == 0 {
+                    code.push(c);
+                }
+            }
+            'B' => {
+                if !(prev == Some('M') && next.is_none()) {
+                    code.push('B');
+                }
+            }
+            'C' => {
+                if next == Some('H') || (next == Some('I') && after == Some('A')) {
+                    code.push_str(if prev == Some('S') { "K" } else { "X" });
+                } else if matches!(next, Some('I' | 'E' | 'Y')) {
+                    if prev != Some('S') {
+                        code.push('S');
+                    }
+                } else {
+                    code.push('K');
+                }
+            }
+            'D' => {
+                if next == Some('G') && matches!(after, Some('E' | 'I' | 'Y')) {
+                    code.push('J');
+                } else {
+                    code.push('T');
+                }
+            }
+            'G' => {
+                let silent = (next == Some('H') && after.is_some() && !is_vowel(after))
+                    || (next == Some('N') && (after.is_none() || word[i + 2..] == ['E', 'D']))
+                    || (prev == Some('D') && matches!(next, Some('E' | 'I' | 'Y')));
+                if silent {
+                    continue;
+                }
+                if matches!(next, Some('I' | 'E' | 'Y')) {
+                    code.push('J');
+                } else {
+                    code.push('K');
+                }
+            }
+            'H' => {
+                // Silent in digraphs, and after a vowel unless one follows
+                let after_digraph = matches!(prev, Some('C' | 'S' | 'P' | 'T' | 'G'));
+                if !after_digraph && (!is_vowel(prev) || is_vowel(next)) {
+                    code.push('H');
+                }
+            }
+            'K' => {
+                if prev != Some('C') {
+                    code.push('K');
+                }
+            }
+            'P' => code.push(if next == Some('H') { 'F' } else { 'P' }),
+            'Q' => code.push('K'),
+            'S' => {
+                if next == Some('H') || (next == Some('I') && matches!(after, Some('O' | 'A'))) {
+                    code.push('X');
+                } else {
+                    code.push('S');
+                }
+            }
+            'T' => {
+                if next == Some('I') && matches!(after, Some('O' | 'A')) {
+                    code.push('X');
+                } else if next == Some('H') {
+                    code.push('0');
+                } else if !(next == Some('C') && after == Some('H')) {
+                    code.push('T');
+                }
+            }
+            'V' => code.push('F'),
+            'W' | 'Y' => {
+                if is_vowel(next) {
+                    code.push(c);
+                }
+            }
+            'X' => code.push_str("KS"),
+            'Z' => code.push('S'),
+            other => code.push(other),
+        }
+    }
+    code
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,242 @@
+//! Typed model of a Kanoniv identity specification.
+//!
+//! Every field the validator has to report on is optional so that an
+//! incomplete spec still deserializes; the checks in [`crate::validator`]
+//! decide what is actually required. Keys the model does not know about are
+//! kept in `extra`/`params` maps so nothing is lost on a round trip; the one
+//! exception is `decision.thresholds`, where the schema rejects unknown keys.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::blocking;
+use crate::rule_types::{self, Param};
+
+/// API versions understood by this version of the model.
+pub const SUPPORTED_API_VERSIONS: &[&str] = &["kanoniv/v1", "kanoniv/v2"];
+
+/// Weight of a rule that does not declare one.
+pub const DEFAULT_WEIGHT: f64 = 1.0;
+
+/// Similarity a non-exact rule needs when it does not declare a `threshold`.
+pub const DEFAULT_FUZZY_THRESHOLD: f64 = 0.85;
+
+/// Blocking strategy used when `blocking.strategy` is not set.
+pub const DEFAULT_BLOCKING_STRATEGY: &str = "standard";
+
+/// Score aggregation used when `decision.aggregation` is not set.
+pub const DEFAULT_AGGREGATION: &str = "weighted_sum";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Spec {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity: Option<Entity>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<Source>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocking: Option<Blocking>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decision: Option<Decision>,
+    /// Top-level keys not covered by the model
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Entity {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Canonical attribute name -> column in the source table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<BTreeMap<String, String>>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub rule_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    /// Type-specific parameters (e.g. `algorithm`)
+    #[serde(flatten)]
+    pub params: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Blocking {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<String>,
+    #[serde(flatten)]
+    pub params: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Decision {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thresholds: Option<Thresholds>,
+    /// How rule weights combine into a score (`weighted_sum` by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aggregation: Option<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Thresholds {
+    #[serde(rename = "match", default, skip_serializing_if = "Option::is_none")]
+    pub match_: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reject: Option<f64>,
+}
+
+impl Spec {
+    /// Major version from `api_version` (`kanoniv/v2` -> 2).
+    pub fn api_major(&self) -> Option<u32> {
+        self.api_version
+            .as_deref()?
+            .strip_prefix("kanoniv/v")?
+            .parse()
+            .ok()
+    }
+
+    pub fn entity_name(&self) -> Option<&str> {
+        self.entity.as_ref()?.name.as_deref()
+    }
+
+    pub fn thresholds(&self) -> Option<&Thresholds> {
+        self.decision.as_ref()?.thresholds.as_ref()
+    }
+
+    pub fn rule(&self, name: &str) -> Option<&Rule> {
+        self.rules.iter().find(|r| r.name.as_deref() == Some(name))
+    }
+
+    pub fn source(&self, name: &str) -> Option<&Source> {
+        self.sources
+            .iter()
+            .find(|s| s.name.as_deref() == Some(name))
+    }
+
+    /// Attribute names exposed by any source, in first-seen order.
+    pub fn available_fields(&self) -> Vec<&str> {
+        let mut fields: Vec<&str> = Vec::new();
+        for source in &self.sources {
+            for key in source.attributes.iter().flat_map(|a| a.keys()) {
+                if !fields.contains(&key.as_str()) {
+                    fields.push(key);
+                }
+            }
+        }
+        fields
+    }
+}
+
+impl Rule {
+    pub fn weight_or_default(&self) -> f64 {
+        self.weight.unwrap_or(DEFAULT_WEIGHT)
+    }
+
+    /// Declared threshold, else 1.0 for registered types that only agree or
+    /// disagree and [`DEFAULT_FUZZY_THRESHOLD`] for everything else.
+    pub fn threshold_or_default(&self) -> f64 {
+        let all_or_nothing = self
+            .rule_type
+            .as_deref()
+            .and_then(rule_types::lookup)
+            .is_some_and(|rule_type| !rule_type.uses_threshold);
+        let default = if all_or_nothing {
+            1.0
+        } else {
+            DEFAULT_FUZZY_THRESHOLD
+        };
+        self.threshold.unwrap_or(default)
+    }
+
+    /// `params` with the registered default of every parameter left out, so
+    /// `type: fuzzy` and `type: fuzzy, algorithm: jaro_winkler` are the same
+    /// rule.
+    pub fn params_or_default(&self) -> BTreeMap<String, Value> {
+        let mut params = self.params.clone();
+        if let Some(rule_type) = self.rule_type.as_deref().and_then(rule_types::lookup) {
+            fill_defaults(&mut params, rule_type.params);
+        }
+        params
+    }
+
+    /// Comparison algorithm: the `algorithm` parameter if set, else the type.
+    pub fn algorithm(&self) -> Option<&str> {
+        self.params
+            .get("algorithm")
+            .and_then(|a| a.as_str())
+            .or(self.rule_type.as_deref())
+    }
+}
+
+impl Blocking {
+    /// `params` with the registered default of every strategy parameter left
+    /// out.
+    pub fn params_or_default(&self) -> BTreeMap<String, Value> {
+        let mut params = self.params.clone();
+        if let Some(strategy) = blocking::lookup(blocking::strategy_name(self)) {
+            fill_defaults(&mut params, strategy.params);
+        }
+        params
+    }
+}
+
+fn fill_defaults(params: &mut BTreeMap<String, Value>, declared: &'static [Param]) {
+    let defaults: Vec<_> = rule_types::implicit_params(params, declared).collect();
+    for (param, default) in defaults {
+        params.insert(param.name.to_string(), Value::from(default));
+    }
+}
+
+impl Thresholds {
+    /// `match`, defaulting to 1.0 (only a perfect score matches).
+    pub fn match_or_default(&self) -> f64 {
+        self.match_.unwrap_or(1.0)
+    }
+
+    /// `review`, defaulting to the match threshold (no review band).
+    pub fn review_or_default(&self) -> f64 {
+        self.review.unwrap_or_else(|| self.match_or_default())
+    }
+
+    /// `reject`, defaulting to 0.0.
+    pub fn reject_or_default(&self) -> f64 {
+        self.reject.unwrap_or(0.0)
+    }
+}
@@ -0,0 +1,200 @@
+//! Enforcing `identity_version` bumps.
+//!
+//! Downstream consumers key their caches on `identity_version`, so a spec
+//! whose plan changes must also change its version. "Changes" is judged by
+//! the semantic plan hash with `identity_version` itself left out: edits to
+//! descriptions or formatting never require a bump.
+
+use anyhow::Result;
+use std::fmt;
+
+use crate::commands::diff::{compute_diff, Impact, Section};
+use crate::diagnostic::{Code, Diagnostic};
+use crate::hashing;
+use crate::ir;
+use crate::parser;
+
+/// How `identity_version` values are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VersionScheme {
+    /// Any new value counts as a bump.
+    #[default]
+    Any,
+    /// `<prefix><major>.<minor>` (`retail_v1.0`): a breaking change needs a
+    /// higher major version, any other plan change at least a higher minor.
+    MajorMinor,
+}
+
+impl VersionScheme {
+    pub fn parse(name: &str) -> Option<VersionScheme> {
+        match name {
+            "any" => Some(VersionScheme::Any),
+            "major-minor" => Some(VersionScheme::MajorMinor),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for VersionScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            VersionScheme::Any => "any",
+            VersionScheme::MajorMinor => "major-minor",
+        })
+    }
+}
+
+/// An `identity_version` under [`VersionScheme::MajorMinor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub prefix: String,
+    pub major: u64,
+    pub minor: u64,
+}
+
+impl Version {
+    /// Split `retail_v1.2` into `retail_v`, 1 and 2. A missing minor
+    /// (`retail_v3`) reads as 0.
+    pub fn parse(value: &str) -> Option<Version> {
+        let number_start = value
+            .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+            .len();
+        let (prefix, number) = value.split_at(number_start);
+        let (major, minor) = match number.split_once('.') {
+            Some((major, minor)) => (major.parse().ok()?, minor.parse().ok()?),
+            None => (number.parse().ok()?, 0),
+        };
+        Some(Version {
+            prefix: prefix.to_string(),
+            major,
+            minor,
+        })
+    }
+
+    fn next(&self, impact: Impact) -> Version {
+        let (major, minor) = if impact == Impact::Breaking {
+            (self.major + 1, 0)
+        } else {
+            (self.major, self.minor + 1)
+        };
+        Version {
+            prefix: self.prefix.clone(),
+            major,
+            minor,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}.{}", self.prefix, self.major, self.minor)
+    }
+}
+
+/// Check that going from `old` to `new` bumps `identity_version` as far as
+/// `scheme` requires. Returns one diagnostic on `identity_version` if not.
+pub fn check_bump(old: &str, new: &str, scheme: VersionScheme) -> Result<Vec<Diagnostic>> {
+    let old_plan = ir::lower(&parser::parse_yaml(old)?)?;
+    let new_plan = ir::lower(&parser::parse_yaml(new)?)?;
+    let old_version = old_plan.identity_version.clone();
+    let new_version = new_plan.identity_version.clone();
+
+    if unversioned_hash(old_plan)? == unversioned_hash(new_plan)? {
+        return Ok(Vec::new());
+    }
+
+    // The bump a release needs follows from its worst change, not counting
+    // the version edit itself
+    let impact = compute_diff(old, new)?
+        .changes
+        .iter()
+        .filter(|c| {
+            !(c.section == Section::Spec
+                && c.change.as_ref().map(|f| f.field.as_str()) == Some("identity_version"))
+        })
+        .map(|c| c.impact)
+        .max()
+        .unwrap_or(Impact::Safe);
+
+    let not_bumped = |message: String, help: String| {
+        Ok(vec![Diagnostic::error(
+            Code::IdentityVersionNotBumped,
+            "identity_version",
+            message,
+        )
+        .with_help(help)])
+    };
+    let unchanged = || {
+        format!(
+            "The plan changed but identity_version is still '{}'",
+            new_version
+        )
+    };
+
+    if scheme == VersionScheme::Any {
+        if new_version == old_version {
+            return not_bumped(
+                unchanged(),
+                "Set a new identity_version so consumers drop cached results".to_string(),
+            );
+        }
+        return Ok(Vec::new());
+    }
+
+    let Some(old) = Version::parse(&old_version) else {
+        return not_bumped(
+            format!(
+                "Previous identity_version '{}' does not follow the {} scheme",
+                old_version, scheme
+            ),
+            "Use <prefix><major>.<minor>, e.g. 'retail_v1.0'".to_string(),
+        );
+    };
+    let help = format!(
+        "Use '{}' or later for a {} change",
+        old.next(impact),
+        impact
+    );
+    let Some(new) = Version::parse(&new_version) else {
+        return not_bumped(
+            format!(
+                "identity_version '{}' does not follow the {} scheme",
+                new_version, scheme
+            ),
+            help,
+        );
+    };
+
+    if new.prefix != old.prefix {
+        return not_bumped(
+            format!(
+                "identity_version prefix changed from '{}' to '{}'",
+                old.prefix, new.prefix
+            ),
+            help,
+        );
+    }
+    let bumped = match impact {
+        Impact::Breaking => new.major > old.major,
+        _ => (new.major, new.minor) > (old.major, old.minor),
+    };
+    if bumped {
+        Ok(Vec::new())
+    } else if new_version == old_version {
+        not_bumped(unchanged(), help)
+    } else {
+        not_bumped(
+            format!(
+                "A {} change needs a bigger bump than '{}' -> '{}'",
+                impact, old_version, new_version
+            ),
+            help,
+        )
+    }
+}
+
+/// Plan hash with `identity_version` blanked, so only the plan content counts.
+fn unversioned_hash(mut plan: ir::Plan) -> Result<String> {
+    plan.identity_version.clear();
+    hashing::plan_hash(&plan)
+}
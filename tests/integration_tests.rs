@@ -1,9 +1,9 @@
-use assert_cmd::Command;
+use assert_cmd::cargo::cargo_bin_cmd;
 use predicates::prelude::*;
 
 #[test]
 fn test_validate_minimal_success() {
-    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    let mut cmd = cargo_bin_cmd!("kanoniv");
     cmd.arg("validate").arg("tests/fixtures/valid/minimal.yaml");
 
     cmd.assert()
@@ -13,7 +13,7 @@ fn test_validate_minimal_success() {
 
 #[test]
 fn test_validate_missing_entity_failure() {
-    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    let mut cmd = cargo_bin_cmd!("kanoniv");
     cmd.arg("validate")
         .arg("tests/fixtures/invalid/missing_entity.yaml");
 
@@ -24,7 +24,7 @@ fn test_validate_missing_entity_failure() {
 
 #[test]
 fn test_validate_unknown_field_failure() {
-    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    let mut cmd = cargo_bin_cmd!("kanoniv");
     cmd.arg("validate")
         .arg("tests/fixtures/invalid/unknown_field.yaml");
 
@@ -35,10 +35,1414 @@ fn test_validate_unknown_field_failure() {
 
 #[test]
 fn test_hash_success() {
-    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    let mut cmd = cargo_bin_cmd!("kanoniv");
     cmd.arg("hash").arg("tests/fixtures/valid/minimal.yaml");
 
     cmd.assert()
         .success()
         .stdout(predicate::str::starts_with("sha256:"));
 }
+
+#[test]
+fn test_parse_yaml_typed_model() {
+    let content = std::fs::read_to_string("tests/fixtures/valid/minimal.yaml").unwrap();
+    let spec = kanoniv_core::parse_yaml(&content).unwrap();
+
+    assert_eq!(spec.api_major(), Some(2));
+    assert_eq!(spec.entity_name(), Some("customer"));
+    assert_eq!(spec.sources[0].id.as_deref(), Some("contact_id"));
+    assert_eq!(spec.rules[0].rule_type.as_deref(), Some("exact"));
+    assert_eq!(spec.rules[0].weight, Some(1.0));
+    assert_eq!(spec.thresholds().and_then(|t| t.match_), Some(0.9));
+}
+
+#[test]
+fn test_validate_reports_source_position() {
+    let mut cmd = cargo_bin_cmd!("kanoniv");
+    cmd.arg("validate")
+        .arg("tests/fixtures/invalid/unknown_field.yaml");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "at tests/fixtures/invalid/unknown_field.yaml:15:5",
+    ));
+}
+
+#[test]
+fn test_source_map_locates_paths() {
+    let content = std::fs::read_to_string("tests/fixtures/valid/minimal.yaml").unwrap();
+    let (_, source_map) = kanoniv_core::parser::parse_yaml_with_spans(&content).unwrap();
+
+    let span = source_map.locate("rules[0].weight").unwrap();
+    assert_eq!((span.line, span.column), (16, 5));
+
+    // Missing nodes fall back to their closest ancestor
+    let span = source_map.locate("rules[0].threshold").unwrap();
+    assert_eq!((span.line, span.column), (13, 5));
+}
+
+#[test]
+fn test_validate_json_reports_diagnostic_codes() {
+    let mut cmd = cargo_bin_cmd!("kanoniv");
+    cmd.arg("validate")
+        .arg("tests/fixtures/invalid/missing_entity.yaml")
+        .arg("--format")
+        .arg("json");
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains(r#""code": "K0001""#))
+        .stdout(predicate::str::contains(
+            r#""name": "missing-required-field""#,
+        ));
+}
+
+#[test]
+fn test_validate_yaml_diagnostics() {
+    let content = std::fs::read_to_string("tests/fixtures/invalid/unknown_field.yaml").unwrap();
+    let diagnostics = kanoniv_core::validate_yaml_diagnostics(&content).unwrap();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].code,
+        kanoniv_core::Code::UnknownFieldReference
+    );
+    assert_eq!(diagnostics[0].path, "rules[0].field");
+    assert!(diagnostics[0].span.is_some());
+
+    // The string wrapper keeps its original shape
+    let errors = kanoniv_core::validate_yaml(&content).unwrap();
+    assert_eq!(
+        errors,
+        vec!["Rule 'unknown_field' references unknown field 'unknown_field'.".to_string()]
+    );
+}
+
+#[test]
+fn test_validate_schema_type_and_enum_errors() {
+    let mut cmd = cargo_bin_cmd!("kanoniv");
+    cmd.arg("validate")
+        .arg("tests/fixtures/invalid/wrong_type.yaml");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "error[K0009]: rules[0].weight: expected number",
+        ))
+        .stderr(predicate::str::contains("error[K0010]: blocking.strategy"))
+        .stderr(predicate::str::contains(
+            "error[K0011]: Unknown field(s) in decision.thresholds: mach",
+        ));
+}
+
+#[test]
+fn test_schemas_accept_what_the_baseline_accepted() {
+    // Unknown top-level keys pass, and v1 knows every blocking strategy
+    let minimal = std::fs::read_to_string("tests/fixtures/valid/minimal.yaml").unwrap();
+    let extended = format!(
+        "{}owner: identity-team\nblocking:\n  strategy: sorted_neighborhood\n  keys: [email]\n  window: 5\n",
+        minimal.replace("kanoniv/v2", "kanoniv/v1")
+    );
+    assert_eq!(
+        kanoniv_core::validate_yaml(&extended).unwrap(),
+        Vec::<String>::new()
+    );
+
+    // Wrongly typed values are reported, not returned as an `Err`
+    let content = std::fs::read_to_string("tests/fixtures/invalid/wrong_type.yaml").unwrap();
+    let errors = kanoniv_core::validate_yaml(&content).unwrap();
+    assert!(errors.iter().any(|e| e.contains("expected number")));
+}
+
+#[test]
+fn test_embedded_schemas_per_api_version() {
+    for version in kanoniv_core::spec::SUPPORTED_API_VERSIONS {
+        let schema = kanoniv_core::schema::schema_for(version).unwrap();
+        assert!(schema["title"].as_str().unwrap().contains(version));
+    }
+    assert!(kanoniv_core::schema::schema_for("kanoniv/v99").is_none());
+}
+
+#[test]
+fn test_schema_prints_requested_version() {
+    let mut cmd = cargo_bin_cmd!("kanoniv");
+    cmd.arg("schema").arg("--api-version").arg("kanoniv/v1");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("(kanoniv/v1)"));
+}
+
+#[test]
+fn test_schema_writes_modeline() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("identity.yaml");
+    std::fs::copy("tests/fixtures/valid/minimal.yaml", &spec).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("kanoniv");
+    cmd.current_dir(dir.path())
+        .arg("schema")
+        .arg("-o")
+        .arg("kanoniv.schema.json")
+        .arg("--modeline")
+        .arg("identity.yaml");
+    cmd.assert().success();
+
+    let content = std::fs::read_to_string(&spec).unwrap();
+    assert!(content.starts_with("# yaml-language-server: $schema=./kanoniv.schema.json\n"));
+    assert!(dir.path().join("kanoniv.schema.json").exists());
+}
+
+#[test]
+fn test_schema_modeline_requires_output() {
+    let mut cmd = cargo_bin_cmd!("kanoniv");
+    cmd.arg("schema")
+        .arg("--modeline")
+        .arg("tests/fixtures/valid/minimal.yaml");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--output <OUTPUT>"));
+}
+
+#[test]
+fn test_validate_directory_with_exclude() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("specs/drafts")).unwrap();
+    std::fs::copy(
+        "tests/fixtures/valid/minimal.yaml",
+        dir.path().join("specs/minimal.yaml"),
+    )
+    .unwrap();
+    std::fs::copy(
+        "tests/fixtures/invalid/unknown_field.yaml",
+        dir.path().join("specs/unknown_field.yml"),
+    )
+    .unwrap();
+    std::fs::copy(
+        "tests/fixtures/invalid/missing_entity.yaml",
+        dir.path().join("specs/drafts/missing_entity.yaml"),
+    )
+    .unwrap();
+    // A link back to the directory is not followed
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(".", dir.path().join("specs/loop")).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("kanoniv");
+    cmd.current_dir(dir.path())
+        .arg("validate")
+        .arg("specs")
+        .arg("--exclude")
+        .arg("drafts");
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("✓ specs/minimal.yaml"))
+        .stdout(predicate::str::contains("1 valid, 1 invalid"))
+        .stderr(predicate::str::contains("✗ specs/unknown_field.yml"))
+        .stderr(predicate::str::contains("missing_entity").not());
+}
+
+#[test]
+fn test_validate_stdin() {
+    let mut cmd = cargo_bin_cmd!("kanoniv");
+    cmd.arg("validate")
+        .arg("-")
+        .write_stdin(std::fs::read_to_string("tests/fixtures/valid/minimal.yaml").unwrap());
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("<stdin> is valid"));
+}
+
+#[test]
+fn test_explain_pair() {
+    let mut cmd = cargo_bin_cmd!("kanoniv");
+    cmd.arg("explain")
+        .arg("tests/fixtures/explain/customer.yaml")
+        .arg("tests/fixtures/explain/pair.json")
+        .arg("--format")
+        .arg("json");
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let explanation: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+    assert_eq!(explanation["band"], "match");
+    assert!((explanation["score"].as_f64().unwrap() - 0.9).abs() < 1e-9);
+    assert_eq!(explanation["rules"][2]["passed"], false);
+    assert_eq!(explanation["rules"][2]["note"], "missing value");
+}
+
+#[test]
+fn test_explain_evaluates_every_rule_type() {
+    use kanoniv_core::rule_types::{ParamKind, ValueKind, RULE_TYPES};
+    use serde_json::json;
+
+    let passes = |rule: &str, left: serde_json::Value, right: serde_json::Value| {
+        let yaml = format!(
+            "api_version: kanoniv/v2\nidentity_version: v1\nentity:\n  name: person\nrules:\n  - name: r\n    field: f\n{}",
+            rule
+        );
+        let spec = kanoniv_core::parse_yaml(&yaml).unwrap();
+        let record = |value| json!({ "f": value }).as_object().unwrap().clone();
+        let outcome =
+            &kanoniv_core::scoring::explain(&spec, &record(left), &record(right)).rules[0];
+        assert!(outcome.similarity.is_some(), "{}: {:?}", rule, outcome.note);
+        outcome.passed
+    };
+
+    // Equal values agree under every registered type
+    for rule_type in RULE_TYPES {
+        let mut rule = format!("    type: {}\n", rule_type.name);
+        for param in rule_type.params.iter().filter(|p| p.required) {
+            let value = match param.kind {
+                ParamKind::Number { min, .. } => min.to_string(),
+                ParamKind::Integer { min } => min.to_string(),
+                ParamKind::OneOf(choices) => choices[0].to_string(),
+            };
+            rule.push_str(&format!("    {}: {}\n", param.name, value));
+        }
+        let value = match rule_type.values {
+            ValueKind::Number => json!(30),
+            ValueKind::Date => json!("2024-02-27"),
+            ValueKind::Text | ValueKind::Any => json!("Smith"),
+        };
+        assert!(passes(&rule, value.clone(), value), "{}", rule_type.name);
+    }
+
+    let numeric = "    type: numeric_range\n    tolerance: 2\n";
+    assert!(passes(numeric, json!(30), json!("31.5")));
+    assert!(!passes(numeric, json!(30), json!(33)));
+    let dates = "    type: date_proximity\n    max_days: 3\n";
+    assert!(passes(dates, json!("2024-02-27"), json!("2024-03-01")));
+    assert!(!passes(
+        dates,
+        json!("2023-02-27"),
+        json!("2023-03-03T10:00:00Z")
+    ));
+    let metaphone = "    type: phonetic\n    algorithm: metaphone\n";
+    assert!(passes(metaphone, json!("Knight"), json!("Night")));
+    assert!(passes(metaphone, json!("Smith"), json!("Smyth")));
+    assert!(passes(metaphone, json!("Philip"), json!("Filip")));
+    assert!(!passes(metaphone, json!("Smith"), json!("Schmidt")));
+}
+
+#[test]
+fn test_compile_to_ir_keeps_rules_and_defaults() {
+    let content = std::fs::read_to_string("tests/fixtures/explain/customer.yaml").unwrap();
+    let spec = kanoniv_core::parse_yaml(&content).unwrap();
+    let plan = kanoniv_core::commands::compile::compile_to_ir(&spec).unwrap();
+
+    assert_eq!(plan.rules.len(), 3);
+    assert_eq!(plan.rules[0].threshold, 1.0);
+    assert_eq!(plan.rules[1].threshold, 0.9);
+    assert_eq!(plan.rules[1].inputs[0].source, "crm");
+    assert_eq!(plan.rules[1].inputs[0].column, "last_name");
+    assert_eq!(plan.sources[0].id, "contact_id");
+    assert_eq!(plan.blocking.strategy, "standard");
+    assert_eq!(plan.decision.aggregation, "weighted_sum");
+    assert!(plan.plan_hash.starts_with("sha256:"));
+}
+
+#[test]
+fn test_compile_rejects_invalid_spec() {
+    let mut cmd = cargo_bin_cmd!("kanoniv");
+    cmd.arg("compile")
+        .arg("tests/fixtures/invalid/unknown_field.yaml");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Cannot compile"));
+}
+
+#[test]
+fn test_ir_validate_compiled_plan() {
+    let dir = tempfile::tempdir().unwrap();
+    let plan = dir.path().join("plan.json");
+
+    cargo_bin_cmd!("kanoniv")
+        .arg("compile")
+        .arg("tests/fixtures/explain/customer.yaml")
+        .arg("-o")
+        .arg(&plan)
+        .assert()
+        .success();
+
+    let compiled: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&plan).unwrap()).unwrap();
+    assert_eq!(compiled["ir_version"], kanoniv_core::IR_VERSION);
+
+    cargo_bin_cmd!("kanoniv")
+        .arg("ir")
+        .arg("validate")
+        .arg(&plan)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("is a valid plan"));
+
+    let future = dir.path().join("future.json");
+    std::fs::write(&future, r#"{"ir_version": "99"}"#).unwrap();
+    cargo_bin_cmd!("kanoniv")
+        .arg("ir")
+        .arg("validate")
+        .arg(&future)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("K0014"));
+}
+
+#[test]
+fn test_canonical_json() {
+    let value = serde_json::json!({
+        "b": [1.0, 0.85, 1e21, 1e-7, -0.0],
+        "a": {"é": "x\n", "Z": null},
+    });
+
+    assert_eq!(
+        kanoniv_core::canonicalize(&value),
+        r#"{"a":{"Z":null,"é":"x\n"},"b":[1,0.85,1e+21,1e-7,0]}"#
+    );
+}
+
+#[test]
+fn test_hash_matches_compiled_plan_hash() {
+    let hash = cargo_bin_cmd!("kanoniv")
+        .arg("hash")
+        .arg("tests/fixtures/explain/customer.yaml")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let content = std::fs::read_to_string("tests/fixtures/explain/customer.yaml").unwrap();
+    let spec = kanoniv_core::parse_yaml(&content).unwrap();
+    let plan = kanoniv_core::commands::compile::compile_to_ir(&spec).unwrap();
+
+    assert_eq!(String::from_utf8(hash).unwrap().trim(), plan.plan_hash);
+    assert_eq!(kanoniv_core::plan_hash(&plan).unwrap(), plan.plan_hash);
+}
+
+#[test]
+fn test_hash_ignores_cosmetic_differences() {
+    let hash = |file: &str, strict: bool| {
+        let mut cmd = cargo_bin_cmd!("kanoniv");
+        cmd.arg("hash").arg(file);
+        if strict {
+            cmd.arg("--strict");
+        }
+        cmd.assert().success().get_output().stdout.clone()
+    };
+
+    let original = "tests/fixtures/explain/customer.yaml";
+    let reformatted = "tests/fixtures/hash/reformatted.yaml";
+    assert_eq!(hash(original, false), hash(reformatted, false));
+    assert_ne!(hash(original, true), hash(reformatted, true));
+}
+
+#[test]
+fn test_hash_and_diff_agree_on_defaults_and_key_order() {
+    let compile = |yaml: &str| {
+        let spec = kanoniv_core::parse_yaml(yaml).unwrap();
+        kanoniv_core::commands::compile::compile_to_ir(&spec).unwrap()
+    };
+
+    let content = std::fs::read_to_string("tests/fixtures/explain/customer.yaml").unwrap();
+    let blocked = format!("{}blocking:\n  keys: [email, phone]\n", content);
+    let explicit = blocked
+        .replace(
+            "threshold: 0.9",
+            "threshold: 0.9\n    algorithm: jaro_winkler",
+        )
+        .replace("[email, phone]", "[phone, email]");
+
+    // `algorithm: jaro_winkler` is the fuzzy default, and keys are a set
+    assert_eq!(compile(&blocked).plan_hash, compile(&explicit).plan_hash);
+    let diff = kanoniv_core::compute_diff(&blocked, &explicit).unwrap();
+    assert!(diff.changes.is_empty());
+}
+
+#[test]
+fn test_hash_tree_tracks_changed_components() {
+    let compile = |yaml: &str| {
+        let spec = kanoniv_core::parse_yaml(yaml).unwrap();
+        kanoniv_core::commands::compile::compile_to_ir(&spec).unwrap()
+    };
+
+    let content = std::fs::read_to_string("tests/fixtures/explain/customer.yaml").unwrap();
+    let before = compile(&content);
+    let after = compile(&content.replace("threshold: 0.9", "threshold: 0.8"));
+
+    assert_ne!(before.plan_hash, after.plan_hash);
+    assert_eq!(before.rules[0].hash, after.rules[0].hash);
+    assert_ne!(before.rules[1].hash, after.rules[1].hash);
+    assert_eq!(before.sources[0].hash, after.sources[0].hash);
+    assert_eq!(before.decision.hash, after.decision.hash);
+
+    cargo_bin_cmd!("kanoniv")
+        .arg("hash")
+        .arg("tests/fixtures/explain/customer.yaml")
+        .arg("--tree")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "{}  rules.last_name_fuzzy",
+            before.rules[1].hash
+        )));
+}
+
+#[test]
+fn test_hash_lockfile() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("customer.yaml");
+    let lock = dir.path().join("kanoniv.lock");
+    let content = std::fs::read_to_string("tests/fixtures/explain/customer.yaml").unwrap();
+    std::fs::write(&spec, &content).unwrap();
+
+    let hash = |args: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("kanoniv");
+        cmd.arg("hash")
+            .arg(&spec)
+            .arg("--lockfile")
+            .arg(&lock)
+            .args(args);
+        cmd.assert()
+    };
+
+    hash(&["--update-lock"]).success();
+    hash(&["--locked"]).success();
+
+    std::fs::write(&spec, content.replace("weight: 0.6", "weight: 0.5")).unwrap();
+    hash(&["--locked"])
+        .failure()
+        .stderr(predicate::str::contains("plan hash changed"))
+        .stderr(predicate::str::contains("--update-lock"));
+
+    hash(&["--check", "sha256:0000"])
+        .failure()
+        .stderr(predicate::str::contains("expected: sha256:0000"));
+}
+
+#[test]
+fn test_diff_formats() {
+    let diff = |format: &str| {
+        let mut cmd = cargo_bin_cmd!("kanoniv");
+        cmd.arg("diff")
+            .arg("tests/fixtures/diff/v1.yaml")
+            .arg("tests/fixtures/diff/v2.yaml")
+            .arg("--format")
+            .arg(format);
+        cmd.assert().success().get_output().stdout.clone()
+    };
+
+    let json: serde_json::Value = serde_json::from_slice(&diff("json")).unwrap();
+    assert_eq!(json["rules_modified"][0]["name"], "email_exact");
+    assert_eq!(json["rules_modified"][0]["new_value"], "0.5");
+
+    let markdown = String::from_utf8(diff("markdown")).unwrap();
+    assert!(markdown.contains("| Modified | `rule email_exact` | `weight`: `0.6` → `0.5` |"));
+
+    let unified = String::from_utf8(diff("unified")).unwrap();
+    assert!(unified.starts_with("--- tests/fixtures/diff/v1.yaml\n"));
+    assert!(unified.contains("\n-    weight: 0.6\n+    weight: 0.5\n"));
+}
+
+#[test]
+fn test_diff_covers_every_component() {
+    let v1 = std::fs::read_to_string("tests/fixtures/diff/v1.yaml").unwrap();
+    let v3 = std::fs::read_to_string("tests/fixtures/diff/v3.yaml").unwrap();
+    let diff = kanoniv_core::compute_diff(&v1, &v3).unwrap();
+
+    let fields = |changes: &[kanoniv_core::commands::diff::FieldChange]| {
+        changes
+            .iter()
+            .map(|c| (c.field.clone(), c.old_value.clone(), c.new_value.clone()))
+            .collect::<Vec<_>>()
+    };
+    let triple = |f: &str, o: &str, n: &str| (f.to_string(), o.to_string(), n.to_string());
+
+    assert!(fields(&diff.spec_changes).contains(&triple("entity.name", "customer", "person")));
+    assert_eq!(diff.sources_added, vec!["shop"]);
+    assert_eq!(diff.sources_modified[1].change.field, "attributes.email");
+    assert_eq!(diff.rules_modified[0].change.field, "type");
+    assert_eq!(diff.rules_modified[0].change.old_value, "exact");
+    assert_eq!(
+        fields(&diff.blocking_changes),
+        vec![triple("keys", "[]", r#"["email"]"#)]
+    );
+    assert_eq!(
+        fields(&diff.decision_changes),
+        vec![triple("thresholds.match", "0.85", "0.8")]
+    );
+    assert!(diff.thresholds_changed);
+}
+
+#[test]
+fn test_diff_impact_and_fail_on() {
+    use kanoniv_core::commands::diff::Impact;
+
+    let v1 = std::fs::read_to_string("tests/fixtures/diff/v1.yaml").unwrap();
+    let v3 = std::fs::read_to_string("tests/fixtures/diff/v3.yaml").unwrap();
+    let diff = kanoniv_core::compute_diff(&v1, &v3).unwrap();
+
+    let impact_of = |field: &str| {
+        diff.changes
+            .iter()
+            .find(|c| c.change.as_ref().is_some_and(|c| c.field == field))
+            .map(|c| c.impact)
+    };
+    assert_eq!(impact_of("identity_version"), Some(Impact::Safe));
+    assert_eq!(impact_of("entity.name"), Some(Impact::Breaking));
+    assert_eq!(impact_of("type"), Some(Impact::Risky));
+    assert_eq!(impact_of("thresholds.match"), Some(Impact::Breaking));
+    assert_eq!(diff.impact(), Some(Impact::Breaking));
+
+    let diff = |target: &str, level: &str| {
+        let mut cmd = cargo_bin_cmd!("kanoniv");
+        cmd.arg("diff")
+            .arg("tests/fixtures/diff/v1.yaml")
+            .arg(target)
+            .arg("--fail-on")
+            .arg(level);
+        cmd.assert()
+    };
+    // v2 only changes a weight, which is risky but not breaking
+    diff("tests/fixtures/diff/v2.yaml", "breaking").success();
+    diff("tests/fixtures/diff/v2.yaml", "risky")
+        .failure()
+        .stderr(predicate::str::contains("--fail-on risky"));
+    diff("tests/fixtures/diff/v3.yaml", "breaking").failure();
+}
+
+#[test]
+fn test_diff_detects_renames() {
+    use kanoniv_core::commands::diff::{compute_diff_with, DiffOptions};
+
+    let v1 = std::fs::read_to_string("tests/fixtures/diff/v1.yaml").unwrap();
+    let renamed = std::fs::read_to_string("tests/fixtures/diff/renamed.yaml").unwrap();
+
+    // email_exact is renamed as-is; last_name_fuzzy also changes its weight
+    let diff = kanoniv_core::compute_diff(&v1, &renamed).unwrap();
+    assert_eq!(diff.rules_renamed.len(), 1);
+    assert_eq!(diff.rules_renamed[0].old_name, "email_exact");
+    assert_eq!(diff.rules_renamed[0].new_name, "email_match");
+    assert_eq!(diff.rules_renamed[0].similarity, 1.0);
+    assert_eq!(diff.rules_added, vec!["surname_fuzzy"]);
+    assert_eq!(diff.rules_removed, vec!["last_name_fuzzy"]);
+
+    let loose = DiffOptions {
+        rename_threshold: 0.7,
+    };
+    let diff = compute_diff_with(&v1, &renamed, &loose).unwrap();
+    assert_eq!(diff.rules_renamed.len(), 2);
+    assert_eq!(diff.rules_renamed[1].similarity, 0.75);
+    assert!(diff.rules_added.is_empty());
+    assert_eq!(diff.rules_modified[0].name, "surname_fuzzy");
+    assert_eq!(diff.rules_modified[0].change.field, "weight");
+
+    cargo_bin_cmd!("kanoniv")
+        .arg("diff")
+        .arg("tests/fixtures/diff/v1.yaml")
+        .arg("tests/fixtures/diff/renamed.yaml")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "renamed: email_exact -> email_match",
+        ));
+}
+
+#[test]
+fn test_diff_rename_threshold_range() {
+    cargo_bin_cmd!("kanoniv")
+        .arg("diff")
+        .arg("tests/fixtures/diff/v1.yaml")
+        .arg("tests/fixtures/diff/renamed.yaml")
+        .arg("--rename-threshold")
+        .arg("1.5")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("must be between 0 and 1"));
+}
+
+#[test]
+fn test_diff_compares_numbers_by_value() {
+    let content = std::fs::read_to_string("tests/fixtures/explain/customer.yaml").unwrap();
+    let v1 = content.replace(
+        "rules:\n",
+        "rules:\n  - name: age_close\n    type: numeric_range\n    field: age\n    tolerance: 2\n",
+    );
+    let v2 = v1.replace("tolerance: 2\n", "tolerance: 2.0\n");
+
+    // The plan hash sees no change, so neither does diff
+    let diff = kanoniv_core::compute_diff(&v1, &v2).unwrap();
+    assert!(diff.is_empty());
+    let renamed = v2.replace("age_close", "age_near");
+    let diff = kanoniv_core::compute_diff(&v1, &renamed).unwrap();
+    assert_eq!(diff.rules_renamed[0].similarity, 1.0);
+}
+
+#[test]
+fn test_hash_lockfile_keys_are_relative_to_the_lock() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("specs")).unwrap();
+    std::fs::copy(
+        "tests/fixtures/explain/customer.yaml",
+        dir.path().join("specs/customer.yaml"),
+    )
+    .unwrap();
+
+    cargo_bin_cmd!("kanoniv")
+        .current_dir(dir.path().join("specs"))
+        .args(["hash", "./customer.yaml", "--update-lock"])
+        .args(["--lockfile", "../kanoniv.lock"])
+        .assert()
+        .success();
+    let lock: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.path().join("kanoniv.lock")).unwrap())
+            .unwrap();
+    assert!(lock["specs"]["specs/customer.yaml"].is_string());
+
+    // The same entry is found from the lock's own directory
+    cargo_bin_cmd!("kanoniv")
+        .current_dir(dir.path())
+        .args(["hash", "specs/customer.yaml", "--locked"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_validate_stdin_only_once() {
+    let mut cmd = cargo_bin_cmd!("kanoniv");
+    cmd.arg("validate").arg("-").arg("-").write_stdin("");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("can only be given once"));
+}
+
+#[test]
+fn test_diff_against_git_revisions() {
+    use std::process::Command;
+
+    let dir = tempfile::tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(dir.path())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    };
+    let spec = dir.path().join("specs/customer.yaml");
+    std::fs::create_dir(dir.path().join("specs")).unwrap();
+
+    git(&["init", "-q"]);
+    git(&["config", "user.email", "ci@example.com"]);
+    git(&["config", "user.name", "CI"]);
+    std::fs::copy("tests/fixtures/diff/v1.yaml", &spec).unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "v1"]);
+    std::fs::copy("tests/fixtures/diff/v2.yaml", &spec).unwrap();
+
+    // Working tree against the last commit
+    cargo_bin_cmd!("kanoniv")
+        .current_dir(dir.path())
+        .args(["diff", "specs/customer.yaml", "--against", "HEAD"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("specs/customer.yaml@HEAD"))
+        .stdout(predicate::str::contains("weight"));
+
+    git(&["commit", "-q", "-am", "v2"]);
+
+    // Every spec changed in a commit range
+    let output = cargo_bin_cmd!("kanoniv")
+        .current_dir(dir.path())
+        .args(["diff", "--git", "HEAD~1..HEAD", "specs", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let reports: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(reports.as_array().unwrap().len(), 1);
+    assert_eq!(reports[0]["status"], "modified");
+    assert_eq!(reports[0]["diff"]["rules_modified"][0]["field"], "weight");
+
+    cargo_bin_cmd!("kanoniv")
+        .current_dir(dir.path())
+        .args(["diff", "--git", "HEAD"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No spec changes in range."));
+
+    cargo_bin_cmd!("kanoniv")
+        .current_dir(dir.path())
+        .args(["diff", "specs/customer.yaml", "--base", "no-such-rev"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown git revision"));
+}
+
+#[test]
+fn test_identity_version_bump() {
+    use kanoniv_core::{check_bump, Code, VersionScheme};
+
+    let v1 = std::fs::read_to_string("tests/fixtures/diff/v1.yaml").unwrap();
+    let v2 = std::fs::read_to_string("tests/fixtures/diff/v2.yaml").unwrap();
+    let v3 = std::fs::read_to_string("tests/fixtures/diff/v3.yaml").unwrap();
+    let bump = |spec: &str, version: &str| {
+        spec.replace(
+            "identity_version: retail_v1.0",
+            &format!("identity_version: {}", version),
+        )
+    };
+
+    // A weight change without a new version is refused; a cosmetic edit is not
+    let errors = check_bump(&v1, &v2, VersionScheme::Any).unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code, Code::IdentityVersionNotBumped);
+    assert_eq!(errors[0].path, "identity_version");
+    let commented = format!("# reviewed\n{}", v1);
+    assert!(check_bump(&v1, &commented, VersionScheme::Any)
+        .unwrap()
+        .is_empty());
+    assert!(
+        check_bump(&v1, &bump(&v2, "retail_v1.1"), VersionScheme::Any)
+            .unwrap()
+            .is_empty()
+    );
+
+    // major-minor: a minor bump covers a risky change, breaking needs a major
+    let scheme = VersionScheme::MajorMinor;
+    assert!(check_bump(&v1, &bump(&v2, "retail_v1.1"), scheme)
+        .unwrap()
+        .is_empty());
+    assert!(check_bump(&v1, &v3, scheme).unwrap().is_empty());
+    let v3_minor = v3.replace("retail_v2.0", "retail_v1.1");
+    let errors = check_bump(&v1, &v3_minor, scheme).unwrap();
+    assert_eq!(
+        errors[0].help.as_deref(),
+        Some("Use 'retail_v2.0' or later for a breaking change")
+    );
+
+    cargo_bin_cmd!("kanoniv")
+        .arg("diff")
+        .arg("tests/fixtures/diff/v1.yaml")
+        .arg("tests/fixtures/diff/v2.yaml")
+        .arg("--require-version-bump")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "identity_version is still 'retail_v1.0'",
+        ));
+
+    cargo_bin_cmd!("kanoniv")
+        .arg("diff")
+        .arg("tests/fixtures/diff/v1.yaml")
+        .arg("tests/fixtures/diff/v3.yaml")
+        .args(["--require-version-bump", "--version-scheme", "major-minor"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_json_patch_round_trip() {
+    use kanoniv_core::patch;
+
+    let v1 = std::fs::read_to_string("tests/fixtures/diff/v1.yaml").unwrap();
+    let v2 = std::fs::read_to_string("tests/fixtures/diff/v2.yaml").unwrap();
+
+    let output = cargo_bin_cmd!("kanoniv")
+        .arg("diff")
+        .arg("tests/fixtures/diff/v1.yaml")
+        .arg("tests/fixtures/diff/v2.yaml")
+        .args(["--format", "json-patch"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let operations: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        operations,
+        serde_json::json!([{ "op": "replace", "path": "/rules/0/weight", "value": 0.5 }])
+    );
+
+    // A value-only patch reproduces the new version byte for byte
+    let patched = patch::apply(&v1, &patch::diff(&v1, &v2).unwrap()).unwrap();
+    assert!(patched.preserved);
+    assert_eq!(patched.content, v2);
+}
+
+#[test]
+fn test_patch_keeps_comments_and_revalidates() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("identity.yaml");
+    std::fs::copy("tests/fixtures/patch/commented.yaml", &spec).unwrap();
+
+    cargo_bin_cmd!("kanoniv")
+        .arg("patch")
+        .arg(&spec)
+        .arg("tests/fixtures/patch/tune.json")
+        .arg("--in-place")
+        .assert()
+        .success();
+    let patched = std::fs::read_to_string(&spec).unwrap();
+    assert!(patched.contains("api_version: kanoniv/v2   # api"));
+    assert!(patched.contains("identity_version: \"retail_v1.1\""));
+    assert!(patched.contains("  # exact email\n"));
+    assert!(patched.contains("weight: 0.7 # strong"));
+    assert!(patched.contains("match: 0.8"));
+
+    // A new key goes after the rule's last one
+    let add = dir.path().join("add.json");
+    std::fs::write(
+        &add,
+        r#"[{ "op": "add", "path": "/rules/0/threshold", "value": 0.9 }]"#,
+    )
+    .unwrap();
+    cargo_bin_cmd!("kanoniv")
+        .arg("patch")
+        .arg(&spec)
+        .arg(&add)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "weight: 0.7 # strong\n    threshold: 0.9\ndecision:",
+        ))
+        .stdout(predicate::str::contains("  # exact email\n"))
+        .stderr(predicate::str::is_empty());
+
+    // Other structural changes still apply, without the comments
+    let copy = dir.path().join("copy.json");
+    std::fs::write(
+        &copy,
+        r#"[{ "op": "copy", "from": "/rules/0/weight", "path": "/rules/0/threshold" }]"#,
+    )
+    .unwrap();
+    cargo_bin_cmd!("kanoniv")
+        .arg("patch")
+        .arg(&spec)
+        .arg(&copy)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("threshold: 0.7"))
+        .stderr(predicate::str::contains(
+            "Comments and formatting were not kept",
+        ));
+
+    // A patch that breaks the spec is refused and nothing is written
+    let broken = dir.path().join("broken.json");
+    std::fs::write(
+        &broken,
+        r#"[{ "op": "replace", "path": "/rules/0/weight", "value": "high" }]"#,
+    )
+    .unwrap();
+    cargo_bin_cmd!("kanoniv")
+        .arg("patch")
+        .arg(&spec)
+        .arg(&broken)
+        .arg("--in-place")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("left unchanged"));
+    assert_eq!(std::fs::read_to_string(&spec).unwrap(), patched);
+
+    // A failed `test` operation stops the whole patch
+    cargo_bin_cmd!("kanoniv")
+        .arg("patch")
+        .arg(&spec)
+        .arg("tests/fixtures/patch/tune.json")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("value did not match"));
+}
+
+#[test]
+fn test_merge_three_way() {
+    use kanoniv_core::merge::merge;
+
+    let read = |name: &str| {
+        std::fs::read_to_string(format!("tests/fixtures/merge/{}.yaml", name)).unwrap()
+    };
+    let (base, ours, theirs) = (read("base"), read("ours"), read("theirs"));
+
+    // Ours tunes weights and renames last_name_fuzzy; theirs adds a source and
+    // a blocking key and tightens the renamed rule, all of which combine
+    let result = merge(&base, &ours, &theirs).unwrap();
+    assert!(result.is_clean());
+    let merged = kanoniv_core::parse_yaml(&result.content).unwrap();
+    let names: Vec<_> = merged
+        .sources
+        .iter()
+        .map(|s| s.name.clone().unwrap())
+        .collect();
+    assert_eq!(names, vec!["crm", "shop"]);
+    let surname = &merged.rules[1];
+    assert_eq!(surname.name.as_deref(), Some("surname_fuzzy"));
+    assert_eq!(surname.threshold, Some(0.92));
+    assert_eq!(merged.rules[0].weight, Some(0.5));
+    assert_eq!(
+        merged.blocking.as_ref().unwrap().keys,
+        vec!["email", "phone"]
+    );
+    assert_eq!(merged.thresholds().unwrap().match_or_default(), 0.9);
+
+    // Value-only changes on their side keep our comments
+    let tighter = base.replace("match: 0.85", "match: 0.9");
+    let result = merge(&base, &ours, &tighter).unwrap();
+    assert!(result.preserved);
+    assert!(result.content.contains("weight: 0.5 # tuned in Q3"));
+    assert!(result.content.contains("match: 0.9"));
+
+    // Both sides changing the same weight is a conflict on that rule and field
+    let result = merge(&base, &ours, &read("conflict")).unwrap();
+    assert_eq!(result.conflicts.len(), 1);
+    assert_eq!(result.conflicts[0].component, "rule email_exact");
+    assert_eq!(result.conflicts[0].field, "weight");
+
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("merged.yaml");
+    cargo_bin_cmd!("kanoniv")
+        .arg("merge")
+        .arg("tests/fixtures/merge/base.yaml")
+        .arg("tests/fixtures/merge/ours.yaml")
+        .arg("tests/fixtures/merge/conflict.yaml")
+        .arg("-o")
+        .arg(&output)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("rule email_exact: weight"))
+        .stderr(predicate::str::contains(
+            "base: 0.6  ours: 0.5  theirs: 0.7",
+        ));
+    assert!(std::fs::read_to_string(&output)
+        .unwrap()
+        .contains("weight: 0.5 # tuned in Q3"));
+}
+
+#[test]
+fn test_lints_warn_without_failing() {
+    use kanoniv_core::lint::{self, LintOptions};
+    use kanoniv_core::{Code, Severity};
+
+    let content = std::fs::read_to_string("tests/fixtures/lint/noisy.yaml").unwrap();
+    let spec = kanoniv_core::parse_yaml(&content).unwrap();
+    let findings = lint::run(&spec, &LintOptions::default());
+    let codes: Vec<_> = findings.iter().map(|d| (d.code, d.severity)).collect();
+    assert_eq!(
+        codes,
+        vec![
+            (Code::ZeroWeight, Severity::Warning),
+            (Code::UnusedAttribute, Severity::Info),
+            (Code::UnknownBlockingKey, Severity::Warning),
+        ]
+    );
+    assert_eq!(findings[1].path, "sources[0].attributes.fax");
+
+    let minimal = kanoniv_core::parse_yaml(
+        &std::fs::read_to_string("tests/fixtures/valid/minimal.yaml").unwrap(),
+    )
+    .unwrap();
+    let findings = lint::run(&minimal, &LintOptions::default());
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].name, "incomplete-thresholds");
+
+    cargo_bin_cmd!("kanoniv")
+        .args(["validate", "tests/fixtures/lint/noisy.yaml"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("warning[K0201]"));
+
+    cargo_bin_cmd!("kanoniv")
+        .args(["validate", "tests/fixtures/lint/noisy.yaml"])
+        .arg("--deny-warnings")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "2 warning(s) denied by --deny-warnings",
+        ));
+
+    // Info findings never fail, and allowed lints do not run
+    cargo_bin_cmd!("kanoniv")
+        .args(["validate", "tests/fixtures/valid/minimal.yaml"])
+        .arg("--deny-warnings")
+        .assert()
+        .success();
+    cargo_bin_cmd!("kanoniv")
+        .args(["validate", "tests/fixtures/lint/noisy.yaml"])
+        .arg("--deny-warnings")
+        .args(["--allow", "zero-weight", "--allow", "K0204"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_threshold_reachability() {
+    use kanoniv_core::scoring::{Aggregation, ScoreRange};
+
+    assert_eq!(
+        Aggregation::WeightedSum.range(&[0.5, 0.25]),
+        ScoreRange {
+            min: 0.0,
+            max: 0.75
+        }
+    );
+    // 0.6 + 0.3 + 0.1 falls just short of 1.0 in floating point
+    let weights = [0.6, 0.3, 0.1];
+    assert!(Aggregation::WeightedSum.range(&weights).reaches(1.0));
+    assert_eq!(Aggregation::Max.range(&weights).max, 0.6);
+    assert_eq!(Aggregation::WeightedAverage.range(&[0.0]).max, 0.0);
+
+    cargo_bin_cmd!("kanoniv")
+        .args(["validate", "tests/fixtures/thresholds/unreachable.yaml"])
+        .arg("--deny-warnings")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "warning[K0206]: 'match: 0.9' cannot be reached because the rule weights sum to 0.6",
+        ))
+        .stderr(predicate::str::contains("at most 0.6"));
+
+    cargo_bin_cmd!("kanoniv")
+        .args(["validate", "tests/fixtures/thresholds/no_reject.yaml"])
+        .arg("--deny-warnings")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "warning[K0205]: 'review: 0' is at or below the minimum possible score 0",
+        ));
+}
+
+#[test]
+fn test_rule_type_registry() {
+    use kanoniv_core::lint::{self, LintOptions};
+    use kanoniv_core::rule_types;
+
+    assert_eq!(rule_types::suggest("levenstein"), Some("levenshtein"));
+    assert_eq!(rule_types::suggest("zipcode"), None);
+
+    let content = std::fs::read_to_string("tests/fixtures/rules/invalid_params.yaml").unwrap();
+    let spec = kanoniv_core::parse_yaml(&content).unwrap();
+    let errors = kanoniv_core::validate_semantics(&spec).unwrap();
+    let found: Vec<_> = errors
+        .iter()
+        .map(|d| (d.code.as_str(), d.path.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("K0106", "rules[0].type"),
+            ("K0107", "rules[1].algorithm"),
+            ("K0107", "rules[2]"),
+            ("K0107", "rules[3].max_days"),
+            ("K0108", "rules[4].field"),
+        ]
+    );
+    assert_eq!(errors[0].help.as_deref(), Some("Did you mean 'fuzzy'?"));
+
+    cargo_bin_cmd!("kanoniv")
+        .args(["validate", "tests/fixtures/rules/invalid_params.yaml"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Rule 'age_close' (numeric_range) is missing required parameter 'tolerance'",
+        ));
+
+    // Threshold on an exact rule, unknown parameters and implicit defaults
+    // are lints
+    let content = std::fs::read_to_string("tests/fixtures/rules/implicit.yaml").unwrap();
+    let spec = kanoniv_core::parse_yaml(&content).unwrap();
+    let findings = lint::run(&spec, &LintOptions::default());
+    let found: Vec<_> = findings.iter().map(|d| (d.name, d.path.as_str())).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("ignored-parameter", "rules[0].threshold"),
+            ("ignored-parameter", "rules[1].tolerance"),
+            ("implicit-parameter", "rules[1]"),
+        ]
+    );
+    assert_eq!(findings[2].severity, kanoniv_core::Severity::Info);
+}
+
+#[test]
+fn test_threshold_defaults_follow_the_registry() {
+    let rules: Vec<kanoniv_core::spec::Rule> = serde_yaml::from_str(
+        "[{type: phonetic}, {type: date_proximity}, {type: similarity}, {type: custom}]",
+    )
+    .unwrap();
+    let thresholds: Vec<f64> = rules.iter().map(|r| r.threshold_or_default()).collect();
+    assert_eq!(thresholds, vec![1.0, 1.0, 0.85, 0.85]);
+}
+
+#[test]
+fn test_blocking_validation_and_coverage() {
+    let content = std::fs::read_to_string("tests/fixtures/blocking/mistyped.yaml").unwrap();
+    let spec = kanoniv_core::parse_yaml(&content).unwrap();
+    let errors = kanoniv_core::validate_semantics(&spec).unwrap();
+    let found: Vec<_> = errors
+        .iter()
+        .map(|d| (d.code.as_str(), d.path.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("K0110", "blocking.bands"),
+            ("K0110", "blocking"),
+            ("K0109", "blocking.keys[0]"),
+        ]
+    );
+
+    cargo_bin_cmd!("kanoniv")
+        .args(["validate", "tests/fixtures/blocking/mistyped.yaml"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Blocking key 'emial' is not an attribute of any source",
+        ))
+        .stderr(predicate::str::contains("Did you mean 'email'?"))
+        .stderr(predicate::str::contains(
+            "Blocking strategy 'lsh' is missing required parameter 'rows'",
+        ));
+
+    let content = std::fs::read_to_string("tests/fixtures/blocking/lsh.yaml").unwrap();
+    let spec = kanoniv_core::parse_yaml(&content).unwrap();
+    let coverage = kanoniv_core::blocking::coverage(&spec);
+    assert_eq!(coverage.keys[0].columns[0].column, "last_name");
+    assert_eq!(coverage.keys[0].missing_in, vec!["shop"]);
+    let uncovered: Vec<_> = coverage.uncovered().map(|r| r.rule.as_str()).collect();
+    assert_eq!(uncovered, vec!["email_exact"]);
+
+    cargo_bin_cmd!("kanoniv")
+        .args(["coverage", "tests/fixtures/blocking/lsh.yaml"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("last_name → crm.last_name"))
+        .stdout(predicate::str::contains("(not mapped in: shop)"))
+        .stdout(predicate::str::contains(
+            "email_exact on email: no blocking key covers it",
+        ))
+        .stdout(predicate::str::contains(
+            "1 of 2 rule(s) compare a blocked field",
+        ));
+}
+
+#[test]
+fn test_blocking_key_typos_fail_and_unmapped_keys_warn() {
+    use kanoniv_core::lint::{self, LintOptions};
+
+    let content = std::fs::read_to_string("tests/fixtures/blocking/unmapped.yaml").unwrap();
+    let spec = kanoniv_core::parse_yaml(&content).unwrap();
+
+    // `last_nme` is close to `last_name`; `postcode` resembles nothing
+    let errors = kanoniv_core::validate_semantics(&spec).unwrap();
+    let found: Vec<_> = errors
+        .iter()
+        .map(|d| (d.code.as_str(), d.path.as_str()))
+        .collect();
+    assert_eq!(found, vec![("K0109", "blocking.keys[0]")]);
+    assert_eq!(errors[0].help.as_deref(), Some("Did you mean 'last_name'?"));
+
+    let fixed = content.replace("last_nme", "last_name");
+    let spec = kanoniv_core::parse_yaml(&fixed).unwrap();
+    assert!(kanoniv_core::validate_semantics(&spec).unwrap().is_empty());
+    let findings = lint::run(&spec, &LintOptions::default());
+    let found: Vec<_> = findings
+        .iter()
+        .map(|d| (d.code.as_str(), d.path.as_str()))
+        .collect();
+    assert_eq!(found, vec![("K0204", "blocking.keys[1]")]);
+
+    cargo_bin_cmd!("kanoniv")
+        .args(["validate", "tests/fixtures/blocking/unmapped.yaml"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[K0109]"))
+        .stderr(predicate::str::contains("Did you mean 'last_name'?"));
+}
+
+#[test]
+fn test_merge_compares_numbers_by_value() {
+    use kanoniv_core::merge::merge;
+
+    let base = std::fs::read_to_string("tests/fixtures/merge/base.yaml").unwrap();
+    let ours = base.replace("weight: 0.6", "weight: 1");
+    let theirs = base.replace("weight: 0.6", "weight: 1.0");
+
+    let result = merge(&base, &ours, &theirs).unwrap();
+    assert!(result.is_clean());
+    assert!(result.content.contains("weight: 1\n"));
+}
+
+#[test]
+fn test_patch_splices_sequence_items() {
+    use kanoniv_core::patch::{self, Patch};
+
+    let content = std::fs::read_to_string("tests/fixtures/patch/commented.yaml").unwrap();
+    let add: Patch = serde_json::from_str(
+        &std::fs::read_to_string("tests/fixtures/patch/add_rule.json").unwrap(),
+    )
+    .unwrap();
+
+    // The new rule follows the last one, keys ordered like its siblings
+    let added = patch::apply(&content, &add).unwrap();
+    assert!(added.preserved);
+    assert!(added.content.contains(
+        "weight: 1.0 # strong\n  - name: email_fuzzy\n    type: fuzzy\n    field: email\n"
+    ));
+    assert!(added
+        .content
+        .starts_with("api_version: kanoniv/v2   # api\n"));
+
+    // Removing it again gives back the original text
+    let remove: Patch =
+        serde_json::from_str(r#"[{ "op": "remove", "path": "/rules/1" }]"#).unwrap();
+    let removed = patch::apply(&added.content, &remove).unwrap();
+    assert!(removed.preserved);
+    assert_eq!(removed.content, content);
+}
+
+#[test]
+fn test_validate_against_requires_bump_for_absolute_paths() {
+    use std::process::Command;
+
+    let dir = tempfile::tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(dir.path())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    };
+    let spec = dir.path().join("customer.yaml");
+
+    git(&["init", "-q"]);
+    git(&["config", "user.email", "ci@example.com"]);
+    git(&["config", "user.name", "CI"]);
+    std::fs::copy("tests/fixtures/diff/v1.yaml", &spec).unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "v1"]);
+    std::fs::copy("tests/fixtures/diff/v2.yaml", &spec).unwrap();
+
+    cargo_bin_cmd!("kanoniv")
+        .current_dir(dir.path())
+        .arg("validate")
+        .arg(&spec)
+        .args(["--against", "HEAD"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[K0105]"));
+
+    // A spec the revision does not have needs no bump
+    let added = dir.path().join("added.yaml");
+    std::fs::copy("tests/fixtures/diff/v2.yaml", &added).unwrap();
+    cargo_bin_cmd!("kanoniv")
+        .current_dir(dir.path())
+        .arg("validate")
+        .arg(&added)
+        .args(["--against", "HEAD"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_diff_against_resolves_paths_in_the_work_tree() {
+    use std::process::Command;
+
+    let dir = tempfile::tempdir().unwrap();
+    let repo = dir.path().join("repo");
+    std::fs::create_dir_all(repo.join("specs")).unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(&repo)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    };
+    let spec = repo.join("specs/customer.yaml");
+
+    git(&["init", "-q"]);
+    git(&["config", "user.email", "ci@example.com"]);
+    git(&["config", "user.name", "CI"]);
+    std::fs::copy("tests/fixtures/diff/v1.yaml", &spec).unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "v1"]);
+    std::fs::copy("tests/fixtures/diff/v2.yaml", &spec).unwrap();
+
+    // An absolute path, and one outside the current directory's subtree
+    for (cwd, path) in [
+        (repo.clone(), spec.clone()),
+        (repo.join("specs"), spec.clone()),
+        (repo.join("specs"), "../specs/customer.yaml".into()),
+    ] {
+        cargo_bin_cmd!("kanoniv")
+            .current_dir(cwd)
+            .arg("diff")
+            .arg(path)
+            .args(["--against", "HEAD"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("weight"));
+    }
+
+    let outside = dir.path().join("customer.yaml");
+    std::fs::copy("tests/fixtures/diff/v1.yaml", &outside).unwrap();
+    cargo_bin_cmd!("kanoniv")
+        .current_dir(&repo)
+        .arg("diff")
+        .arg(&outside)
+        .args(["--against", "HEAD"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("outside the git work tree"));
+}
+
+#[test]
+fn test_diff_git_range_keeps_deleted_and_outside_specs() {
+    use std::process::Command;
+
+    let dir = tempfile::tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(dir.path())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    };
+    std::fs::create_dir_all(dir.path().join("specs")).unwrap();
+    std::fs::create_dir_all(dir.path().join("docs")).unwrap();
+    std::fs::write(
+        dir.path().join("docs/README.md"),
+        "specs live in ../specs\n",
+    )
+    .unwrap();
+    let spec = dir.path().join("specs/customer.yaml");
+
+    git(&["init", "-q"]);
+    git(&["config", "user.email", "ci@example.com"]);
+    git(&["config", "user.name", "CI"]);
+    std::fs::copy("tests/fixtures/diff/v1.yaml", &spec).unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "v1"]);
+
+    // A spec outside the current directory is shown with a `../` path
+    std::fs::copy("tests/fixtures/diff/v2.yaml", &spec).unwrap();
+    cargo_bin_cmd!("kanoniv")
+        .current_dir(dir.path().join("docs"))
+        .args(["diff", "--git", "HEAD", ".."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("../specs/customer.yaml@HEAD"))
+        .stdout(predicate::str::contains("weight"));
+
+    // A spec deleted together with its directory
+    git(&["rm", "-q", "-r", "-f", "specs"]);
+    git(&["commit", "-q", "-m", "drop specs"]);
+    let output = cargo_bin_cmd!("kanoniv")
+        .current_dir(dir.path())
+        .args(["diff", "--git", "HEAD~1..HEAD", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let reports: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(reports[0]["status"], "deleted");
+}